@@ -0,0 +1,44 @@
+//! Compares the time SHAKE and LINCS take for many constrained dimers, and for chains.
+//!
+//! Run it with `cargo bench --features bench`.
+
+use std::time::Instant;
+
+use bibber::constraints::{
+    chains, ConstraintSolver, Lincs, DEFAULT_LINCS_ITERATIONS, DEFAULT_LINCS_ORDER,
+};
+use bibber::vec3::Vec3;
+
+const BOUNDARY: Vec3 = Vec3::new(3e-9, 3e-9, 3e-9);
+const DT: f64 = 2e-15;
+
+fn main() {
+    let solvers = [
+        ConstraintSolver::default(),
+        ConstraintSolver::Lincs(Lincs {
+            order: DEFAULT_LINCS_ORDER,
+            iterations: DEFAULT_LINCS_ITERATIONS,
+        }),
+    ];
+    for solver in &solvers {
+        for (n, length) in [(10000, 2), (2500, 5)] {
+            let (constraints, reference, particles) = chains(n, length, DT);
+            let repeats = 100;
+            let start = Instant::now();
+            for _ in 0..repeats {
+                let mut constrained = particles.clone();
+                solver
+                    .positions(&constraints, &reference, &mut constrained, BOUNDARY, DT)
+                    .unwrap();
+                solver
+                    .velocities(&constraints, &mut constrained, BOUNDARY, DT)
+                    .unwrap();
+            }
+            let elapsed = start.elapsed().as_secs_f64() / repeats as f64;
+            println!(
+                "{solver:?} on chains of {length}: {:.3} ms per step",
+                elapsed * 1e3
+            );
+        }
+    }
+}
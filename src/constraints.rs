@@ -0,0 +1,1026 @@
+use crate::universe::{minimum_image, Particle};
+use crate::vec3::Vec3;
+
+/// Default relative tolerance on the constrained lengths.
+pub const DEFAULT_CONSTRAINT_TOLERANCE: f64 = 1e-8;
+/// Default maximum number of iterations of SHAKE and RATTLE.
+pub const DEFAULT_MAX_ITERATIONS: usize = 1000;
+/// Default order of the LINCS matrix expansion.
+pub const DEFAULT_LINCS_ORDER: usize = 4;
+/// Default number of LINCS corrections for the lengthening due to rotation.
+pub const DEFAULT_LINCS_ITERATIONS: usize = 1;
+
+/// A fixed distance between two particles.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Constraint {
+    /// Indices of the constrained particles.
+    pub particles: (usize, usize),
+    /// Constrained length (meter).
+    pub length: f64,
+}
+
+impl Constraint {
+    /// Returns this constraint with the indices of its particles shifted by some offset.
+    pub(crate) fn offset(self, offset: usize) -> Self {
+        let (i, j) = self.particles;
+        Self {
+            particles: (i + offset, j + offset),
+            ..self
+        }
+    }
+}
+
+/// A rigid triangle of an oxygen and two hydrogens, like a water molecule, that is kept in shape
+/// by SETTLE rather than by the constraint solver.
+///
+/// Both hydrogens must have the same mass.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Settle {
+    /// Indices of the oxygen and the two hydrogens.
+    pub particles: (usize, usize, usize),
+    /// Distance between the oxygen and either hydrogen (meter).
+    pub oh: f64,
+    /// Distance between the hydrogens (meter).
+    pub hh: f64,
+}
+
+impl Settle {
+    /// Returns this triangle with the indices of its particles shifted by some offset.
+    pub(crate) fn offset(self, offset: usize) -> Self {
+        let (o, h1, h2) = self.particles;
+        Self {
+            particles: (o + offset, h1 + offset, h2 + offset),
+            ..self
+        }
+    }
+
+    /// Returns the three constraints that make up the triangle.
+    pub(crate) fn constraints(&self) -> [Constraint; 3] {
+        let (o, h1, h2) = self.particles;
+        [(o, h1, self.oh), (o, h2, self.oh), (h1, h2, self.hh)].map(|(i, j, length)| Constraint {
+            particles: (i, j),
+            length,
+        })
+    }
+
+    /// Moves the particles back onto the triangle after an unconstrained update from the
+    /// `reference` positions, by solving for the rigid rotation analytically. Returns the virial
+    /// of the constraint forces.
+    ///
+    /// See Miyamoto, S. & Kollman, P. A. (1992). J. Comput. Chem. 13, 952.
+    fn positions(
+        &self,
+        reference: &[Vec3],
+        particles: &mut [Particle],
+        boundary: Vec3,
+        dt: f64,
+    ) -> Result<f64, ConstraintError> {
+        let (o, h1, h2) = self.particles;
+        let distorted = ConstraintError::Distorted {
+            particles: self.particles,
+        };
+        let (mo, mh) = (particles[o].mass, particles[h1].mass);
+        // The canonical triangle has its oxygen at a distance ra from the centre of mass, and its
+        // hydrogens at rc to either side of the point at rb on the other side.
+        let rc = 0.5 * self.hh;
+        let height = (self.oh * self.oh - rc * rc).sqrt();
+        let ra = 2.0 * mh * height / (mo + 2.0 * mh);
+        let rb = height - ra;
+
+        // The sides of the triangle before the step, and the unconstrained positions after the
+        // step relative to their centre of mass.
+        let b0 = minimum_image(reference[h1] - reference[o], boundary);
+        let c0 = minimum_image(reference[h2] - reference[o], boundary);
+        let d1 = minimum_image(particles[h1].pos - particles[o].pos, boundary);
+        let d2 = minimum_image(particles[h2].pos - particles[o].pos, boundary);
+        let a1 = -(d1 + d2) * (mh / (mo + 2.0 * mh));
+        let b1 = d1 + a1;
+        let c1 = d2 + a1;
+
+        // A frame with its z axis normal to the triangle before the step, and the oxygen in its
+        // yz plane.
+        let z = b0.cross(c0);
+        let x = a1.cross(z);
+        let y = z.cross(x);
+        let [x, y, z] = [x, y, z].map(|axis| axis / axis.norm());
+        let local = |r: Vec3| Vec3::new(x.dot(r), y.dot(r), z.dot(r));
+        let (b0, c0, a1, b1, c1) = (local(b0), local(c0), local(a1), local(b1), local(c1));
+
+        // The tilt of the triangle out of the plane follows from the heights of its particles.
+        let sin_phi = a1.z / ra;
+        let cos_phi_2 = 1.0 - sin_phi * sin_phi;
+        if cos_phi_2.is_nan() {
+            return Err(ConstraintError::NotFinite { particles: (o, h1) });
+        }
+        if cos_phi_2 <= 0.0 {
+            return Err(distorted);
+        }
+        let cos_phi = cos_phi_2.sqrt();
+        let sin_psi = (b1.z - c1.z) / (2.0 * rc * cos_phi);
+        let cos_psi_2 = 1.0 - sin_psi * sin_psi;
+        if cos_psi_2 <= 0.0 {
+            return Err(distorted);
+        }
+        let cos_psi = cos_psi_2.sqrt();
+        let ya2 = ra * cos_phi;
+        let xb2 = -rc * cos_psi;
+        let yb2 = -rb * cos_phi - rc * sin_psi * sin_phi;
+        let yc2 = -rb * cos_phi + rc * sin_psi * sin_phi;
+
+        // The rotation within the plane follows from the conservation of angular momentum.
+        let alpha = xb2 * (b0.x - c0.x) + b0.y * yb2 + c0.y * yc2;
+        let beta = xb2 * (c0.y - b0.y) + b0.x * yb2 + c0.x * yc2;
+        let gamma = b0.x * b1.y - b1.x * b0.y + c0.x * c1.y - c1.x * c0.y;
+        let alpha_beta_2 = alpha * alpha + beta * beta;
+        let discriminant = alpha_beta_2 - gamma * gamma;
+        if discriminant < 0.0 {
+            return Err(distorted);
+        }
+        let sin_theta = (alpha * gamma - beta * discriminant.sqrt()) / alpha_beta_2;
+        let cos_theta = (1.0 - sin_theta * sin_theta).sqrt();
+        let a3 = Vec3::new(-ya2 * sin_theta, ya2 * cos_theta, a1.z);
+        let b3 = Vec3::new(
+            xb2 * cos_theta - yb2 * sin_theta,
+            xb2 * sin_theta + yb2 * cos_theta,
+            b1.z,
+        );
+        let c3 = Vec3::new(
+            -xb2 * cos_theta - yc2 * sin_theta,
+            -xb2 * sin_theta + yc2 * cos_theta,
+            c1.z,
+        );
+
+        let global = |r: Vec3| x * r.x + y * r.y + z * r.z;
+        let displacements =
+            [(o, a3 - a1), (h1, b3 - b1), (h2, c3 - c1)].map(|(i, d)| (i, global(d)));
+        for (i, displacement) in displacements {
+            particles[i].pos += displacement;
+            particles[i].vel += displacement / dt;
+            if !(particles[i].pos.dot(particles[i].vel)).is_finite() {
+                return Err(ConstraintError::NotFinite { particles: (o, i) });
+            }
+        }
+        // The displacement m * Δr = 1/2 * F * Δt^2 corresponds to a force on each particle.
+        let (_, dh1) = displacements[1];
+        let (_, dh2) = displacements[2];
+        Ok(2.0 * mh * (global(b0).dot(dh1) + global(c0).dot(dh2)) / (dt * dt))
+    }
+
+    /// Removes the velocities along the sides of the triangle, by solving for the three
+    /// corrections at once. Returns the virial of the constraint forces.
+    fn velocities(
+        &self,
+        particles: &mut [Particle],
+        boundary: Vec3,
+        dt: f64,
+    ) -> Result<f64, ConstraintError> {
+        let constraints = self.constraints();
+        let r = constraints.map(|c| {
+            let (i, j) = c.particles;
+            minimum_image(particles[i].pos - particles[j].pos, boundary)
+        });
+        let directions = r.map(|r| r / r.norm());
+        // A correction τ_d along constraint d = (p, q) changes the velocities by
+        // Δv_p = -τ_d / m_p * e_d and Δv_q = τ_d / m_q * e_d.
+        let side = |particle: usize, d: &Constraint| match d.particles {
+            (p, _) if p == particle => -1.0 / particles[particle].mass,
+            (_, q) if q == particle => 1.0 / particles[particle].mass,
+            _ => 0.0,
+        };
+        let rows = [0, 1, 2].map(|c| {
+            let (i, j) = constraints[c].particles;
+            let coefficient = |d: usize| {
+                (side(i, &constraints[d]) - side(j, &constraints[d]))
+                    * directions[c].dot(directions[d])
+            };
+            Vec3::new(coefficient(0), coefficient(1), coefficient(2))
+        });
+        let rhs = [0, 1, 2].map(|c| {
+            let (i, j) = constraints[c].particles;
+            -directions[c].dot(particles[i].vel - particles[j].vel)
+        });
+        // Cramer's rule, with the inverse of the matrix from the cross products of its rows.
+        let determinant = rows[0].dot(rows[1].cross(rows[2]));
+        let corrections = (rows[1].cross(rows[2]) * rhs[0]
+            + rows[2].cross(rows[0]) * rhs[1]
+            + rows[0].cross(rows[1]) * rhs[2])
+            / determinant;
+        let corrections = [corrections.x, corrections.y, corrections.z];
+
+        let mut virial = 0.0;
+        for (d, constraint) in constraints.iter().enumerate() {
+            if !corrections[d].is_finite() {
+                return Err(ConstraintError::NotFinite {
+                    particles: constraint.particles,
+                });
+            }
+            let (p, q) = constraint.particles;
+            let correction = directions[d] * corrections[d];
+            particles[p].vel -= correction / particles[p].mass;
+            particles[q].vel += correction / particles[q].mass;
+            // The change of velocity m * Δv = 1/2 * F * Δt corresponds to a force along the
+            // constraint.
+            virial -= 2.0 * corrections[d] * r[d].norm() / dt;
+        }
+        Ok(virial)
+    }
+}
+
+/// Moves the particles of rigid triangles back onto their shapes after an unconstrained update
+/// of the positions from the `reference` positions over a time step `dt` (seconds), and corrects
+/// their velocities accordingly (SETTLE).
+///
+/// Returns the virial of the constraint forces (J).
+pub fn settle(
+    settles: &[Settle],
+    reference: &[Vec3],
+    particles: &mut [Particle],
+    boundary: Vec3,
+    dt: f64,
+) -> Result<f64, ConstraintError> {
+    settles
+        .iter()
+        .map(|settle| settle.positions(reference, particles, boundary, dt))
+        .sum()
+}
+
+/// Removes the components of the velocities of rigid triangles that would change their shapes.
+///
+/// Returns the virial of the constraint forces (J).
+pub fn settle_velocities(
+    settles: &[Settle],
+    particles: &mut [Particle],
+    boundary: Vec3,
+    dt: f64,
+) -> Result<f64, ConstraintError> {
+    settles
+        .iter()
+        .map(|settle| settle.velocities(particles, boundary, dt))
+        .sum()
+}
+
+/// Failure to satisfy the constraints.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstraintError {
+    /// The constraints were not satisfied within the maximum number of iterations, with some
+    /// largest remaining relative deviation.
+    NotConverged { iterations: usize, deviation: f64 },
+    /// A constrained pair of particles rotated by more than 90 degrees within a single step, such
+    /// that the constraint cannot be corrected along its previous direction.
+    Rotated { particles: (usize, usize) },
+    /// A constrained pair of particles has a position or velocity that is not finite.
+    NotFinite { particles: (usize, usize) },
+    /// A rigid triangle was distorted so much within a single step that it cannot be settled.
+    Distorted { particles: (usize, usize, usize) },
+}
+
+impl std::fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConstraintError::NotConverged {
+                iterations,
+                deviation,
+            } => write!(
+                f,
+                "constraints did not converge within {iterations} iterations, \
+                 with a remaining relative deviation of {deviation:e}"
+            ),
+            ConstraintError::Rotated { particles: (i, j) } => write!(
+                f,
+                "the constraint between particles {i} and {j} rotated by more than 90 degrees \
+                 in a single step"
+            ),
+            ConstraintError::NotFinite { particles: (i, j) } => write!(
+                f,
+                "the constraint between particles {i} and {j} has a position or velocity that \
+                 is not finite"
+            ),
+            ConstraintError::Distorted {
+                particles: (o, h1, h2),
+            } => write!(
+                f,
+                "the rigid triangle of particles {o}, {h1} and {h2} was distorted too much in a \
+                 single step to be settled"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConstraintError {}
+
+/// Moves the particles onto their constraints, by repeatedly scaling the vector between every
+/// constrained pair to its length, until every constraint is satisfied to the relative
+/// tolerance.
+///
+/// Unlike the solvers, this does not depend on the positions before some step, so it can be used
+/// for configurations that are far from satisfying the constraints, such as a start
+/// configuration.
+pub fn relax(
+    constraints: &[Constraint],
+    particles: &mut [Particle],
+    boundary: Vec3,
+    tolerance: f64,
+    max_iterations: usize,
+) -> Result<(), ConstraintError> {
+    let mut deviation = 0.0;
+    for _ in 0..max_iterations {
+        let mut converged = true;
+        deviation = 0.0;
+        for constraint in constraints {
+            let (i, j) = constraint.particles;
+            let r = minimum_image(particles[i].pos - particles[j].pos, boundary);
+            let length = r.norm();
+            let relative = (length - constraint.length).abs() / constraint.length;
+            if !relative.is_finite() || length == 0.0 {
+                return Err(ConstraintError::NotFinite { particles: (i, j) });
+            }
+            if relative > tolerance {
+                converged = false;
+                deviation = f64::max(deviation, relative);
+                // Keep the center of mass of the pair in place.
+                let (mi, mj) = (particles[i].mass, particles[j].mass);
+                let correction = r * (constraint.length / length - 1.0);
+                particles[i].pos += correction * (mj / (mi + mj));
+                particles[j].pos -= correction * (mi / (mi + mj));
+            }
+        }
+        if converged {
+            return Ok(());
+        }
+    }
+    Err(ConstraintError::NotConverged {
+        iterations: max_iterations,
+        deviation,
+    })
+}
+
+/// Algorithm to satisfy the constraints.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstraintSolver {
+    Shake(Shake),
+    Lincs(Lincs),
+}
+
+impl Default for ConstraintSolver {
+    fn default() -> Self {
+        Self::Shake(Shake {
+            tolerance: DEFAULT_CONSTRAINT_TOLERANCE,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        })
+    }
+}
+
+impl ConstraintSolver {
+    /// Moves the particles such that the constraints are satisfied, after an unconstrained update
+    /// of the positions from the `reference` positions over a time step `dt` (seconds).
+    ///
+    /// The velocities are corrected for the displacements. Returns the virial (J) of the
+    /// constraint forces.
+    pub fn positions(
+        &self,
+        constraints: &[Constraint],
+        reference: &[Vec3],
+        particles: &mut [Particle],
+        boundary: Vec3,
+        dt: f64,
+    ) -> Result<f64, ConstraintError> {
+        match self {
+            ConstraintSolver::Shake(shake) => {
+                shake.positions(constraints, reference, particles, boundary, dt)
+            }
+            ConstraintSolver::Lincs(lincs) => {
+                lincs.positions(constraints, reference, particles, boundary, dt)
+            }
+        }
+    }
+
+    /// Removes the components of the velocities along the constraints, such that the constrained
+    /// lengths do not change. Returns the virial (J) of the constraint forces.
+    pub fn velocities(
+        &self,
+        constraints: &[Constraint],
+        particles: &mut [Particle],
+        boundary: Vec3,
+        dt: f64,
+    ) -> Result<f64, ConstraintError> {
+        match self {
+            ConstraintSolver::Shake(shake) => {
+                shake.velocities(constraints, particles, boundary, dt)
+            }
+            ConstraintSolver::Lincs(lincs) => {
+                lincs.velocities(constraints, particles, boundary, dt)
+            }
+        }
+    }
+}
+
+/// Iterative constraint solver that corrects one constraint at a time: SHAKE for the positions,
+/// and RATTLE for the velocities.
+///
+/// SHAKE moves the particles of a constraint along the direction of the constraint before the
+/// update, in proportion to their inverse masses,
+///
+/// ```
+/// g = (d^2 - r_ij^2) / (2 * (1 / m_i + 1 / m_j) * r_ij ⋅ r_ij_ref)
+/// r_i += g / m_i * r_ij_ref
+/// r_j -= g / m_j * r_ij_ref
+/// ```
+///
+/// and RATTLE removes the relative velocity along the constraint in the same way,
+///
+/// ```
+/// k = r_ij ⋅ v_ij / ((1 / m_i + 1 / m_j) * d^2)
+/// v_i -= k / m_i * r_ij
+/// v_j += k / m_j * r_ij
+/// ```
+///
+/// Both are repeated until every constraint is satisfied to the relative tolerance.
+///
+/// See Ryckaert, J.-P., Ciccotti, G. & Berendsen, H. J. C. (1977). J. Comput. Phys. 23, 327, and
+/// Andersen, H. C. (1983). J. Comput. Phys. 52, 24.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shake {
+    /// Relative tolerance on the constrained lengths.
+    pub tolerance: f64,
+    /// Maximum number of iterations over all constraints.
+    pub max_iterations: usize,
+}
+
+impl Shake {
+    fn positions(
+        &self,
+        constraints: &[Constraint],
+        reference: &[Vec3],
+        particles: &mut [Particle],
+        boundary: Vec3,
+        dt: f64,
+    ) -> Result<f64, ConstraintError> {
+        let unconstrained: Vec<Vec3> = particles.iter().map(|p| p.pos).collect();
+        let mut virial = 0.0;
+        let mut deviation = 0.0;
+        for _ in 0..self.max_iterations {
+            let mut converged = true;
+            deviation = 0.0;
+            for constraint in constraints {
+                let (i, j) = constraint.particles;
+                let r = minimum_image(particles[i].pos - particles[j].pos, boundary);
+                let d2 = constraint.length * constraint.length;
+                let difference = d2 - r.dot(r);
+                // |r - d| / d ≈ |d^2 - r^2| / (2 * d^2)
+                let relative = (difference / (2.0 * d2)).abs();
+                // A NaN deviation must not be mistaken for convergence.
+                if !relative.is_finite() {
+                    return Err(ConstraintError::NotFinite { particles: (i, j) });
+                }
+                if relative > self.tolerance {
+                    converged = false;
+                    deviation = f64::max(deviation, relative);
+                    let r_ref = minimum_image(reference[i] - reference[j], boundary);
+                    let projection = r.dot(r_ref);
+                    if projection <= 0.0 {
+                        return Err(ConstraintError::Rotated { particles: (i, j) });
+                    }
+                    let (inv_mi, inv_mj) = (1.0 / particles[i].mass, 1.0 / particles[j].mass);
+                    let g = difference / (2.0 * (inv_mi + inv_mj) * projection);
+                    particles[i].pos += r_ref * (g * inv_mi);
+                    particles[j].pos -= r_ref * (g * inv_mj);
+                    // The displacement m * Δr = 1/2 * F * Δt^2 corresponds to a force along r_ref.
+                    virial += 2.0 * g * r_ref.dot(r_ref) / (dt * dt);
+                }
+            }
+            if converged {
+                for (particle, unconstrained) in particles.iter_mut().zip(unconstrained) {
+                    particle.vel += (particle.pos - unconstrained) / dt;
+                }
+                return Ok(virial);
+            }
+        }
+        Err(ConstraintError::NotConverged {
+            iterations: self.max_iterations,
+            deviation,
+        })
+    }
+
+    fn velocities(
+        &self,
+        constraints: &[Constraint],
+        particles: &mut [Particle],
+        boundary: Vec3,
+        dt: f64,
+    ) -> Result<f64, ConstraintError> {
+        let mut virial = 0.0;
+        let mut deviation = 0.0;
+        for _ in 0..self.max_iterations {
+            let mut converged = true;
+            deviation = 0.0;
+            for constraint in constraints {
+                let (i, j) = constraint.particles;
+                let r = minimum_image(particles[i].pos - particles[j].pos, boundary);
+                let v = particles[i].vel - particles[j].vel;
+                let d2 = constraint.length * constraint.length;
+                let rv = r.dot(v);
+                // The relative change of the length over a time step.
+                let relative = (rv * dt / d2).abs();
+                // A NaN deviation must not be mistaken for convergence.
+                if !relative.is_finite() {
+                    return Err(ConstraintError::NotFinite { particles: (i, j) });
+                }
+                if relative > self.tolerance {
+                    converged = false;
+                    deviation = f64::max(deviation, relative);
+                    let (inv_mi, inv_mj) = (1.0 / particles[i].mass, 1.0 / particles[j].mass);
+                    let k = rv / ((inv_mi + inv_mj) * d2);
+                    particles[i].vel -= r * (k * inv_mi);
+                    particles[j].vel += r * (k * inv_mj);
+                    // The change of velocity m * Δv = 1/2 * F * Δt corresponds to a force along r.
+                    virial -= 2.0 * k * r.dot(r) / dt;
+                }
+            }
+            if converged {
+                return Ok(virial);
+            }
+        }
+        Err(ConstraintError::NotConverged {
+            iterations: self.max_iterations,
+            deviation,
+        })
+    }
+}
+
+/// Linear constraint solver (LINCS), which corrects all constraints at once.
+///
+/// The positions are projected back onto the constraints along their directions before the update,
+/// by solving the linear system
+///
+/// ```
+/// (I - A) * x = S * (B * r - d)
+/// ```
+///
+/// where B holds the directions of the constraints, S = diag(1 / sqrt(1 / m_i + 1 / m_j)), and A
+/// couples the constraints that share a particle. The inverse is approximated by the expansion
+/// (I - A)^-1 = I + A + A^2 + ... up to some order, which converges as long as the constraints
+/// are not coupled too strongly (it does not for triangles of constraints). The lengthening due
+/// to the rotation of the constraints is corrected by a number of further iterations.
+///
+/// See Hess, B., Bekker, H., Berendsen, H. J. C. & Fraaije, J. G. E. M. (1997). J. Comput.
+/// Chem. 18, 1463.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lincs {
+    /// Order of the matrix expansion.
+    pub order: usize,
+    /// Number of corrections for the lengthening due to rotation.
+    pub iterations: usize,
+}
+
+impl Lincs {
+    /// Returns the factor 1 / sqrt(1 / m_i + 1 / m_j) of every constraint, and for every
+    /// constraint the coupling coefficients A with the constraints that share a particle with it.
+    fn coupling(
+        constraints: &[Constraint],
+        directions: &[Vec3],
+        particles: &[Particle],
+    ) -> (Vec<f64>, Vec<Vec<(usize, f64)>>) {
+        let inverse_mass = |i: usize| 1.0 / particles[i].mass;
+        let factors: Vec<f64> = constraints
+            .iter()
+            .map(|c| {
+                let (i, j) = c.particles;
+                1.0 / (inverse_mass(i) + inverse_mass(j)).sqrt()
+            })
+            .collect();
+
+        // Constraints that share a particle are adjacent after sorting the constraints by their
+        // particles.
+        let mut ends: Vec<(usize, usize)> = constraints
+            .iter()
+            .enumerate()
+            .flat_map(|(index, c)| [(c.particles.0, index), (c.particles.1, index)])
+            .collect();
+        ends.sort_unstable();
+        let mut coupling = vec![Vec::new(); constraints.len()];
+        for group in ends.chunk_by(|a, b| a.0 == b.0) {
+            let shared = group[0].0;
+            for &(_, b) in group {
+                for &(_, k) in group.iter().filter(|&&(_, k)| k != b) {
+                    let (i, j) = constraints[b].particles;
+                    let (ki, kj) = constraints[k].particles;
+                    // The sign depends on whether the shared particle is on the same end of both
+                    // constraints.
+                    let sign = if i == ki || j == kj { -1.0 } else { 1.0 };
+                    let coefficient = sign * inverse_mass(shared) * factors[b] * factors[k];
+                    coupling[b].push((k, coefficient * directions[b].dot(directions[k])));
+                }
+            }
+        }
+        (factors, coupling)
+    }
+
+    /// Solves (I - A) * x = rhs by the expansion x = (I + A + A^2 + ...) * rhs.
+    fn solve(&self, coupling: &[Vec<(usize, f64)>], rhs: Vec<f64>) -> Vec<f64> {
+        let mut solution = rhs.clone();
+        let mut term = rhs;
+        for _ in 0..self.order {
+            term = coupling
+                .iter()
+                .map(|coupled| coupled.iter().map(|&(k, a)| a * term[k]).sum())
+                .collect();
+            for (x, t) in solution.iter_mut().zip(&term) {
+                *x += t;
+            }
+        }
+        solution
+    }
+
+    fn positions(
+        &self,
+        constraints: &[Constraint],
+        reference: &[Vec3],
+        particles: &mut [Particle],
+        boundary: Vec3,
+        dt: f64,
+    ) -> Result<f64, ConstraintError> {
+        let directions: Vec<Vec3> = constraints
+            .iter()
+            .map(|c| {
+                let (i, j) = c.particles;
+                let r = minimum_image(reference[i] - reference[j], boundary);
+                r / r.norm()
+            })
+            .collect();
+        let (factors, coupling) = Self::coupling(constraints, &directions, particles);
+        let unconstrained: Vec<Vec3> = particles.iter().map(|p| p.pos).collect();
+        let vector = |particles: &[Particle], c: &Constraint| {
+            let (i, j) = c.particles;
+            minimum_image(particles[i].pos - particles[j].pos, boundary)
+        };
+
+        // Project out the deviations along the old directions, and then correct for the
+        // lengthening due to rotation, such that the projections have the constrained lengths.
+        let mut total = vec![0.0; constraints.len()];
+        for iteration in 0..=self.iterations {
+            let rhs = constraints
+                .iter()
+                .zip(&directions)
+                .zip(&factors)
+                .map(|((c, &direction), &factor)| {
+                    let r = vector(particles, c);
+                    let deviation = if iteration == 0 {
+                        direction.dot(r) - c.length
+                    } else {
+                        let projection = f64::max(2.0 * c.length * c.length - r.dot(r), 0.0);
+                        c.length - projection.sqrt()
+                    };
+                    factor * deviation
+                })
+                .collect();
+            let solution = self.solve(&coupling, rhs);
+            for (b, c) in constraints.iter().enumerate() {
+                let (i, j) = c.particles;
+                let correction = directions[b] * (factors[b] * solution[b]);
+                particles[i].pos -= correction / particles[i].mass;
+                particles[j].pos += correction / particles[j].mass;
+                total[b] += solution[b];
+            }
+        }
+
+        let mut virial = 0.0;
+        for (b, c) in constraints.iter().enumerate() {
+            let r = vector(particles, c);
+            if !(r.dot(r).is_finite() && total[b].is_finite()) {
+                return Err(ConstraintError::NotFinite {
+                    particles: c.particles,
+                });
+            }
+            if r.dot(directions[b]) <= 0.0 {
+                return Err(ConstraintError::Rotated {
+                    particles: c.particles,
+                });
+            }
+            // The displacement m * Δr = 1/2 * F * Δt^2 corresponds to a force along the direction.
+            virial -= 2.0 * factors[b] * total[b] * c.length / (dt * dt);
+        }
+        for (particle, unconstrained) in particles.iter_mut().zip(unconstrained) {
+            particle.vel += (particle.pos - unconstrained) / dt;
+        }
+        Ok(virial)
+    }
+
+    fn velocities(
+        &self,
+        constraints: &[Constraint],
+        particles: &mut [Particle],
+        boundary: Vec3,
+        dt: f64,
+    ) -> Result<f64, ConstraintError> {
+        let directions: Vec<Vec3> = constraints
+            .iter()
+            .map(|c| {
+                let (i, j) = c.particles;
+                let r = minimum_image(particles[i].pos - particles[j].pos, boundary);
+                r / r.norm()
+            })
+            .collect();
+        let (factors, coupling) = Self::coupling(constraints, &directions, particles);
+        let rhs = constraints
+            .iter()
+            .zip(&directions)
+            .zip(&factors)
+            .map(|((c, &direction), &factor)| {
+                let (i, j) = c.particles;
+                factor * direction.dot(particles[i].vel - particles[j].vel)
+            })
+            .collect();
+        let solution = self.solve(&coupling, rhs);
+
+        let mut virial = 0.0;
+        for (b, c) in constraints.iter().enumerate() {
+            if !solution[b].is_finite() {
+                return Err(ConstraintError::NotFinite {
+                    particles: c.particles,
+                });
+            }
+            let (i, j) = c.particles;
+            let correction = directions[b] * (factors[b] * solution[b]);
+            particles[i].vel -= correction / particles[i].mass;
+            particles[j].vel += correction / particles[j].mass;
+            // The change of velocity m * Δv = 1/2 * F * Δt corresponds to a force along the
+            // direction.
+            virial -= 2.0 * factors[b] * solution[b] * c.length / dt;
+        }
+        Ok(virial)
+    }
+}
+
+/// Returns `n` chains of `length` particles connected by constraints, like united-atom alkanes,
+/// displaced as by an unconstrained step of `dt` (seconds), and the reference positions before that
+/// step. The tests and the benchmark of the solvers run on these.
+#[cfg(any(test, feature = "bench"))]
+pub fn chains(n: usize, length: usize, dt: f64) -> (Vec<Constraint>, Vec<Vec3>, Vec<Particle>) {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(5);
+    let mut random = |scale: f64| {
+        Vec3::new(
+            rng.gen_range(-scale..scale),
+            rng.gen_range(-scale..scale),
+            rng.gen_range(-scale..scale),
+        )
+    };
+    let mut constraints = Vec::new();
+    let mut reference = Vec::new();
+    let mut particles = Vec::new();
+    for chain in 0..n {
+        let offset = chain * length;
+        let mut pos = random(1.5e-9);
+        for index in 0..length {
+            if index > 0 {
+                // Zigzag with angles of about 110 degrees between the constraints.
+                let side = if index % 2 == 0 { 1.0 } else { -1.0 };
+                pos += Vec3::new(0.126e-9, side * 0.088e-9, 0.0);
+                constraints.push(Constraint {
+                    particles: (offset + index - 1, offset + index),
+                    length: 0.1538e-9,
+                });
+            }
+            reference.push(pos);
+            let vel = random(1e3);
+            let particle = Particle::new(pos + vel * dt, vel, Vec3::zero(), 14.0 * 1.66e-27);
+            particles.push(particle);
+        }
+    }
+    for constraint in &mut constraints {
+        let (i, j) = constraint.particles;
+        constraint.length = (reference[i] - reference[j]).norm();
+    }
+    (constraints, reference, particles)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    const BOUNDARY: Vec3 = Vec3::new(3e-9, 3e-9, 3e-9);
+    const DT: f64 = 2e-15;
+
+    /// Returns triangles of coupled constraints, like rigid water, with their particles displaced
+    /// as by an unconstrained step, and the reference positions before that step.
+    fn triangles(n: usize) -> (Vec<Constraint>, Vec<Vec3>, Vec<Particle>) {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut random = |scale: f64| {
+            Vec3::new(
+                rng.gen_range(-scale..scale),
+                rng.gen_range(-scale..scale),
+                rng.gen_range(-scale..scale),
+            )
+        };
+        let mut constraints = Vec::new();
+        let mut reference = Vec::new();
+        let mut particles = Vec::new();
+        for triangle in 0..n {
+            let o = random(1.5e-9);
+            let h1 = o + Vec3::new(0.1e-9, 0.0, 0.0);
+            let h2 = o + Vec3::new(-0.0333e-9, 0.0943e-9, 0.0);
+            for (pos, mass) in [(o, 16.0), (h1, 1.0), (h2, 1.0)] {
+                reference.push(pos);
+                let vel = random(1e3);
+                let particle = Particle::new(pos + vel * DT, vel, Vec3::zero(), mass * 1.66e-27);
+                particles.push(particle);
+            }
+            let offset = 3 * triangle;
+            for (i, j) in [(0, 1), (0, 2), (1, 2)] {
+                let length = (reference[offset + i] - reference[offset + j]).norm();
+                let constraint = Constraint {
+                    particles: (i, j),
+                    length,
+                };
+                constraints.push(constraint.offset(offset));
+            }
+        }
+        (constraints, reference, particles)
+    }
+
+    /// Returns the largest relative deviation from the constrained lengths, and the largest
+    /// relative rate of change of the lengths over a time step.
+    fn deviations(constraints: &[Constraint], particles: &[Particle]) -> (f64, f64) {
+        let mut largest = (0.0, 0.0);
+        for constraint in constraints {
+            let (i, j) = constraint.particles;
+            let r = minimum_image(particles[i].pos - particles[j].pos, BOUNDARY);
+            let deviation = (r.norm() - constraint.length).abs() / constraint.length;
+            let v = particles[i].vel - particles[j].vel;
+            let rate = (r.dot(v) * DT / (constraint.length * constraint.length)).abs();
+            largest = (f64::max(largest.0, deviation), f64::max(largest.1, rate));
+        }
+        largest
+    }
+
+    #[test]
+    fn shake_and_rattle_satisfy_constraints() {
+        let (constraints, reference, mut particles) = triangles(20);
+        let solver = ConstraintSolver::default();
+        solver
+            .positions(&constraints, &reference, &mut particles, BOUNDARY, DT)
+            .unwrap();
+        solver
+            .velocities(&constraints, &mut particles, BOUNDARY, DT)
+            .unwrap();
+        let (deviation, rate) = deviations(&constraints, &particles);
+        assert!(
+            deviation < 2.0 * DEFAULT_CONSTRAINT_TOLERANCE,
+            "{deviation}"
+        );
+        assert!(rate <= DEFAULT_CONSTRAINT_TOLERANCE, "{rate}");
+    }
+
+    #[test]
+    fn settle_agrees_with_shake() {
+        let (constraints, reference, particles) = triangles(20);
+        let settles: Vec<Settle> = constraints
+            .chunks(3)
+            .map(|triangle| Settle {
+                particles: (
+                    triangle[0].particles.0,
+                    triangle[0].particles.1,
+                    triangle[1].particles.1,
+                ),
+                oh: triangle[0].length,
+                hh: triangle[2].length,
+            })
+            .collect();
+        // The water-like triangles of the tests are not quite symmetric.
+        let constraints: Vec<Constraint> = settles.iter().flat_map(|s| s.constraints()).collect();
+
+        let mut settled = particles.clone();
+        let mut virial = settle(&settles, &reference, &mut settled, BOUNDARY, DT).unwrap();
+        virial += settle_velocities(&settles, &mut settled, BOUNDARY, DT).unwrap();
+        let (deviation, rate) = deviations(&constraints, &settled);
+        assert!(deviation < 1e-12, "{deviation}");
+        assert!(rate < 1e-12, "{rate}");
+
+        let mut shaken = particles.clone();
+        let solver = ConstraintSolver::Shake(Shake {
+            tolerance: 1e-14,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+        });
+        let mut shake_virial = solver
+            .positions(&constraints, &reference, &mut shaken, BOUNDARY, DT)
+            .unwrap();
+        shake_virial += solver
+            .velocities(&constraints, &mut shaken, BOUNDARY, DT)
+            .unwrap();
+        for (settled, shaken) in settled.iter().zip(&shaken) {
+            assert!((settled.pos - shaken.pos).norm() < 1e-12 * 1e-9);
+            assert!((settled.vel - shaken.vel).norm() < 1e-9 * 1e3);
+        }
+        assert!(
+            (virial - shake_virial).abs() < 1e-9 * shake_virial.abs(),
+            "{virial} {shake_virial}"
+        );
+    }
+
+    #[test]
+    fn lincs_converges_with_its_order() {
+        let mut previous = (f64::INFINITY, f64::INFINITY);
+        for (order, iterations) in [(2, 1), (4, 1), (8, 2), (16, 4)] {
+            let (constraints, reference, mut particles) = chains(20, 8, DT);
+            let solver = ConstraintSolver::Lincs(Lincs { order, iterations });
+            solver
+                .positions(&constraints, &reference, &mut particles, BOUNDARY, DT)
+                .unwrap();
+            solver
+                .velocities(&constraints, &mut particles, BOUNDARY, DT)
+                .unwrap();
+            let (deviation, rate) = deviations(&constraints, &particles);
+            assert!(deviation < previous.0, "{order}: {deviation}");
+            assert!(rate < previous.1, "{order}: {rate}");
+            previous = (deviation, rate);
+        }
+        assert!(previous.0 < 1e-8, "{}", previous.0);
+        assert!(previous.1 < 1e-8, "{}", previous.1);
+
+        // The constraint forces agree with those of SHAKE and RATTLE.
+        let lincs = ConstraintSolver::Lincs(Lincs {
+            order: 16,
+            iterations: 4,
+        });
+        let virials = [ConstraintSolver::default(), lincs].map(|solver| {
+            let (constraints, reference, mut particles) = chains(20, 8, DT);
+            let positions = solver
+                .positions(&constraints, &reference, &mut particles, BOUNDARY, DT)
+                .unwrap();
+            let velocities = solver
+                .velocities(&constraints, &mut particles, BOUNDARY, DT)
+                .unwrap();
+            (positions, velocities)
+        });
+        assert!((virials[0].0 - virials[1].0).abs() < 1e-3 * virials[0].0.abs());
+        assert!((virials[0].1 - virials[1].1).abs() < 1e-3 * virials[0].1.abs());
+
+        // Uncoupled constraints are solved exactly, apart from the rotation.
+        let (constraints, reference, mut particles) = chains(100, 2, DT);
+        let solver = ConstraintSolver::Lincs(Lincs {
+            order: 0,
+            iterations: 2,
+        });
+        solver
+            .positions(&constraints, &reference, &mut particles, BOUNDARY, DT)
+            .unwrap();
+        solver
+            .velocities(&constraints, &mut particles, BOUNDARY, DT)
+            .unwrap();
+        let (deviation, rate) = deviations(&constraints, &particles);
+        assert!(deviation < 1e-8, "{deviation}");
+        assert!(rate < 1e-12, "{rate}");
+    }
+
+    #[test]
+    fn solvers_satisfy_dimers_and_chains() {
+        let solvers = [
+            ConstraintSolver::default(),
+            ConstraintSolver::Lincs(Lincs {
+                order: DEFAULT_LINCS_ORDER,
+                iterations: DEFAULT_LINCS_ITERATIONS,
+            }),
+        ];
+        for solver in &solvers {
+            for (n, length) in [(100, 2), (25, 5)] {
+                let (constraints, reference, mut particles) = chains(n, length, DT);
+                solver
+                    .positions(&constraints, &reference, &mut particles, BOUNDARY, DT)
+                    .unwrap();
+                solver
+                    .velocities(&constraints, &mut particles, BOUNDARY, DT)
+                    .unwrap();
+                let (deviation, rate) = deviations(&constraints, &particles);
+                // The default expansion order of LINCS is accurate to about 1e-5 for chains.
+                assert!(deviation < 1e-4, "{solver:?}, {length}: {deviation}");
+                assert!(rate < 1e-4, "{solver:?}, {length}: {rate}");
+            }
+        }
+    }
+
+    #[test]
+    fn failure_to_converge_is_an_error() {
+        let (constraints, reference, mut particles) = triangles(20);
+        let solver = ConstraintSolver::Shake(Shake {
+            tolerance: DEFAULT_CONSTRAINT_TOLERANCE,
+            max_iterations: 2,
+        });
+        let result = solver.positions(&constraints, &reference, &mut particles, BOUNDARY, DT);
+        assert!(matches!(result, Err(ConstraintError::NotConverged { .. })));
+
+        // A particle that has gone missing is not mistaken for a satisfied constraint.
+        let (constraints, reference, mut particles) = triangles(1);
+        particles[1].pos = Vec3::new(f64::NAN, 0.0, 0.0);
+        let result = ConstraintSolver::default().positions(
+            &constraints,
+            &reference,
+            &mut particles,
+            BOUNDARY,
+            DT,
+        );
+        assert!(matches!(result, Err(ConstraintError::NotFinite { .. })));
+    }
+}
@@ -0,0 +1,839 @@
+use std::f64::consts::PI;
+
+use crate::fft::{fft3, Complex, Direction};
+use crate::universe::Particle;
+use crate::vec3::Vec3;
+
+/// Coulomb constant, 1 / (4 * π * ε_0) (N⋅m^2⋅C^−2).
+pub const COULOMB: f64 = 8.9875517923e9;
+/// Elementary charge (C).
+pub const ELEMENTARY_CHARGE: f64 = 1.602176634e-19;
+/// Default relative strength of the real-space Ewald interaction at the cutoff.
+pub const DEFAULT_EWALD_TOLERANCE: f64 = 1e-5;
+/// Default largest distance between PME grid points (meter).
+pub const DEFAULT_PME_SPACING: f64 = 0.12e-9;
+/// Default order of the PME B-splines.
+pub const DEFAULT_PME_ORDER: usize = 4;
+/// Default damping parameter of the Wolf summation (1 / meter).
+pub const DEFAULT_WOLF_ALPHA: f64 = 2.0e9;
+
+/// Treatment of the electrostatic interactions between charged particles.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Electrostatics {
+    /// Charges do not interact.
+    #[default]
+    None,
+    /// Ewald summation.
+    Ewald(Ewald),
+    /// Smooth particle-mesh Ewald summation.
+    Pme(Pme),
+    /// Reaction field beyond the cutoff.
+    ReactionField(ReactionField),
+    /// Damped shifted-force Wolf summation.
+    Wolf(Wolf),
+}
+
+impl Electrostatics {
+    /// Returns the energy (J) and the magnitude of the force (N) between a pair of particles
+    /// within the cutoff, with the product of their charges `qq` (C^2) at a distance `r`
+    /// (meter).
+    #[inline]
+    pub fn pair(&self, qq: f64, r: f64) -> (f64, f64) {
+        match self {
+            Electrostatics::None => (0.0, 0.0),
+            Electrostatics::Ewald(ewald) => real_space(ewald.alpha, qq, r),
+            Electrostatics::Pme(pme) => real_space(pme.alpha, qq, r),
+            Electrostatics::ReactionField(reaction_field) => reaction_field.pair(qq, r),
+            Electrostatics::Wolf(wolf) => wolf.pair(qq, r),
+        }
+    }
+
+    /// Returns the energy (J) and the magnitude of the force (N) of the plain Coulomb interaction
+    /// between a pair of particles, with the product of their charges `qq` (C^2) at a distance
+    /// `r` (meter).
+    ///
+    /// This is used for pairs that are treated apart from the nonbonded interactions, such as
+    /// 1-4 pairs. Without electrostatics, charges do not interact at all.
+    ///
+    /// ```
+    /// V(r) = k_e * q_i * q_j / r
+    /// ```
+    #[inline]
+    pub fn coulomb(&self, qq: f64, r: f64) -> (f64, f64) {
+        match self {
+            Electrostatics::None => (0.0, 0.0),
+            _ => {
+                let energy = COULOMB * qq / r;
+                (energy, energy / r)
+            }
+        }
+    }
+
+    /// Returns the energy (J) and the magnitude of the force (N) that must be added for a pair
+    /// of particles of which the nonbonded interactions are excluded, with the product of their
+    /// charges `qq` (C^2) at a distance `r` (meter).
+    ///
+    /// The reciprocal-space sums of Ewald and PME include every pair, so the part of the Coulomb
+    /// interaction that they cover is subtracted again.
+    ///
+    /// ```
+    /// V_excl(r) = - k_e * q_i * q_j * erf(α * r) / r
+    /// ```
+    ///
+    /// The reaction field polarized by a molecule acts on its own charges as well, so excluded
+    /// pairs keep the reaction-field part of their interaction, as in GROMACS.
+    #[inline]
+    pub fn excluded(&self, qq: f64, r: f64) -> (f64, f64) {
+        let alpha = match self {
+            Electrostatics::None | Electrostatics::Wolf(_) => return (0.0, 0.0),
+            Electrostatics::ReactionField(reaction_field) => return reaction_field.excluded(qq, r),
+            Electrostatics::Ewald(ewald) => ewald.alpha,
+            Electrostatics::Pme(pme) => pme.alpha,
+        };
+        // V_excl = V_real - V_coulomb
+        let (energy, force) = real_space(alpha, qq, r);
+        let coulomb = COULOMB * qq / r;
+        (energy - coulomb, force - coulomb / r)
+    }
+
+    /// Adds the forces due to the interactions that are not covered by [`Electrostatics::pair`],
+    /// and returns their energy (J) and virial (J).
+    pub fn long_range(
+        &self,
+        particles: &[Particle],
+        boundary: Vec3,
+        forces: &mut [Vec3],
+    ) -> (f64, f64) {
+        match self {
+            Electrostatics::None | Electrostatics::ReactionField(_) => (0.0, 0.0),
+            Electrostatics::Ewald(ewald) => {
+                let (energy, virial) = ewald.reciprocal_space(particles, boundary, forces);
+                (energy + self_energy(ewald.alpha, particles), virial)
+            }
+            Electrostatics::Pme(pme) => {
+                let (energy, virial) = pme.reciprocal_space(particles, boundary, forces);
+                (energy + self_energy(pme.alpha, particles), virial)
+            }
+            Electrostatics::Wolf(wolf) => (wolf.self_energy(particles), 0.0),
+        }
+    }
+}
+
+/// Ewald summation of the electrostatic interactions under periodic boundary conditions.
+///
+/// The Coulomb interaction is split into a short-ranged real-space part that is summed over the
+/// pairs within the cutoff,
+///
+/// ```
+/// V_real(r) = k_e * q_i * q_j * erfc(α * r) / r
+/// ```
+///
+/// and a smooth long-ranged part that is summed over the reciprocal lattice vectors k,
+///
+/// ```
+/// E_recip = k_e * 2 * π / V * Σ_{k ≠ 0} exp(-k^2 / (4 * α^2)) / k^2 * |S(k)|^2
+/// S(k) = Σ_j q_j * exp(i * k ⋅ r_j)
+/// ```
+///
+/// minus the interaction of every charge with its own screening charge,
+///
+/// ```
+/// E_self = -k_e * α / sqrt(π) * Σ_i q_i^2
+/// ```
+///
+/// The system is assumed to be neutral.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ewald {
+    /// Splitting parameter (1 / meter).
+    pub alpha: f64,
+    /// Largest reciprocal lattice vector index along each axis.
+    pub kmax: [usize; 3],
+}
+
+impl Ewald {
+    /// Creates a new [`Ewald`] summation for some cutoff (meter) and boundary, such that both the
+    /// real-space interaction at the cutoff and the reciprocal-space terms that are left out are
+    /// smaller than some relative tolerance.
+    pub fn from_tolerance(cutoff: f64, boundary: Vec3, tolerance: f64) -> Self {
+        let alpha = splitting_parameter(cutoff, tolerance);
+        // The terms fall off as exp(-k^2 / (4 * α^2)).
+        let k_limit = 2.0 * alpha * (-tolerance.ln()).sqrt();
+        let kmax = |length: f64| (k_limit * length / (2.0 * PI)).ceil() as usize;
+        Self {
+            alpha,
+            kmax: [kmax(boundary.x), kmax(boundary.y), kmax(boundary.z)],
+        }
+    }
+
+    /// Adds the reciprocal-space forces, and returns the reciprocal-space energy (J) and virial
+    /// (J).
+    ///
+    /// ```
+    /// F_i = k_e * 4 * π * q_i / V * Σ_{k ≠ 0} k * exp(-k^2 / (4 * α^2)) / k^2
+    ///       * [ Re S(k) * sin(k ⋅ r_i) - Im S(k) * cos(k ⋅ r_i) ]
+    /// W = Σ_{k ≠ 0} E_k * (1 - k^2 / (2 * α^2))
+    /// ```
+    fn reciprocal_space(
+        &self,
+        particles: &[Particle],
+        boundary: Vec3,
+        forces: &mut [Vec3],
+    ) -> (f64, f64) {
+        let volume = boundary.x * boundary.y * boundary.z;
+        let [kx_max, ky_max, kz_max] = self.kmax.map(|k| k as isize);
+        let k_unit = Vec3::new(2.0 * PI, 2.0 * PI, 2.0 * PI) / boundary;
+        let k_limit2 = (k_unit.x * kx_max as f64)
+            .min(k_unit.y * ky_max as f64)
+            .min(k_unit.z * kz_max as f64)
+            .powi(2);
+
+        // The phases exp(i * k ⋅ r_j) are products of the phases along each axis, which are
+        // tabulated for every particle and every index along that axis.
+        let phases = |kmax: isize, k: f64, coordinate: fn(&Particle) -> f64| {
+            particles
+                .iter()
+                .map(|p| {
+                    (-kmax..=kmax)
+                        .map(|n| {
+                            let phase = n as f64 * k * coordinate(p);
+                            (phase.cos(), phase.sin())
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let phases_x = phases(kx_max, k_unit.x, |p| p.pos.x);
+        let phases_y = phases(ky_max, k_unit.y, |p| p.pos.y);
+        let phases_z = phases(kz_max, k_unit.z, |p| p.pos.z);
+
+        let mut energy = 0.0;
+        let mut virial = 0.0;
+        let mut particle_phases = vec![(0.0, 0.0); particles.len()];
+        for nx in -kx_max..=kx_max {
+            for ny in -ky_max..=ky_max {
+                for nz in -kz_max..=kz_max {
+                    if (nx, ny, nz) == (0, 0, 0) {
+                        continue;
+                    }
+                    let k = Vec3::new(nx as f64, ny as f64, nz as f64) * k_unit;
+                    let k2 = k.x * k.x + k.y * k.y + k.z * k.z;
+                    // Leave out the corners of the box of k-vectors, to keep the sum spherical.
+                    if k2 > k_limit2 {
+                        continue;
+                    }
+                    let (ix, iy, iz) = (
+                        (nx + kx_max) as usize,
+                        (ny + ky_max) as usize,
+                        (nz + kz_max) as usize,
+                    );
+
+                    // S(k) = Σ_j q_j * exp(i * k ⋅ r_j)
+                    let (mut re_s, mut im_s) = (0.0, 0.0);
+                    for (j, particle) in particles.iter().enumerate() {
+                        let (cx, sx) = phases_x[j][ix];
+                        let (cy, sy) = phases_y[j][iy];
+                        let (cz, sz) = phases_z[j][iz];
+                        // Multiply the complex numbers (cx + i sx) (cy + i sy) (cz + i sz).
+                        let (cxy, sxy) = (cx * cy - sx * sy, cx * sy + sx * cy);
+                        let (c, s) = (cxy * cz - sxy * sz, cxy * sz + sxy * cz);
+                        particle_phases[j] = (c, s);
+                        re_s += particle.charge * c;
+                        im_s += particle.charge * s;
+                    }
+
+                    let factor = (-k2 / (4.0 * self.alpha * self.alpha)).exp() / k2;
+                    let e_k = COULOMB * 2.0 * PI / volume * factor * (re_s * re_s + im_s * im_s);
+                    energy += e_k;
+                    virial += e_k * (1.0 - k2 / (2.0 * self.alpha * self.alpha));
+
+                    let force_factor = COULOMB * 4.0 * PI / volume * factor;
+                    for ((particle, force), &(c, s)) in particles
+                        .iter()
+                        .zip(forces.iter_mut())
+                        .zip(&particle_phases)
+                    {
+                        *force += k * (force_factor * particle.charge * (re_s * s - im_s * c));
+                    }
+                }
+            }
+        }
+
+        (energy, virial)
+    }
+}
+
+/// Smooth particle-mesh Ewald summation (Essmann et al., 1995).
+///
+/// The real-space part and the self energy are those of the [`Ewald`] summation. For the
+/// reciprocal-space part, the charges are spread onto a grid with cardinal B-splines, such that
+/// the structure factor S(k) can be approximated with a fast Fourier transform of the grid.
+///
+/// ```
+/// S(k) ≈ b(m) * F(Q)(m)
+/// E_recip = k_e * 2 * π / V * Σ_{m ≠ 0} exp(-k^2 / (4 * α^2)) / k^2 * |b(m)|^2 * |F(Q)(m)|^2
+/// ```
+///
+/// where Q is the charge grid, and k = 2 * π * m / L.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pme {
+    /// Splitting parameter (1 / meter).
+    pub alpha: f64,
+    /// Order of the B-splines. A higher order is more accurate, but spreads every charge over
+    /// more grid points.
+    pub order: usize,
+    /// Number of grid points along each axis.
+    pub dims: [usize; 3],
+}
+
+impl Pme {
+    /// Creates a new [`Pme`] summation for some cutoff (meter) and boundary, with a real-space
+    /// interaction at the cutoff that is smaller than some relative tolerance.
+    ///
+    /// The number of grid points along each axis is the smallest power of two for which the
+    /// distance between grid points is at most `spacing` (meter), and that is at least the
+    /// `order`.
+    pub fn new(cutoff: f64, boundary: Vec3, tolerance: f64, spacing: f64, order: usize) -> Self {
+        let points = |length: f64| {
+            ((length / spacing).ceil() as usize)
+                .max(order)
+                .next_power_of_two()
+        };
+        Self {
+            alpha: splitting_parameter(cutoff, tolerance),
+            order,
+            dims: [points(boundary.x), points(boundary.y), points(boundary.z)],
+        }
+    }
+
+    /// Adds the reciprocal-space forces, and returns the reciprocal-space energy (J) and virial
+    /// (J).
+    ///
+    /// The energy can be written as a convolution, E = 1/2 * Σ_g Q(g) * (θ ⋆ Q)(g), where the
+    /// Fourier transform of θ is C(m) = k_e * 4 * π / V * exp(-k^2 / (4 * α^2)) / k^2 * |b(m)|^2.
+    /// The force on a particle is then the gradient of its B-spline weights on the grid, weighed
+    /// by the convolution.
+    fn reciprocal_space(
+        &self,
+        particles: &[Particle],
+        boundary: Vec3,
+        forces: &mut [Vec3],
+    ) -> (f64, f64) {
+        let [nx, ny, nz] = self.dims;
+        let volume = boundary.x * boundary.y * boundary.z;
+        let index = |x: usize, y: usize, z: usize| (x * ny + y) * nz + z;
+
+        // Spread the charges onto the grid.
+        let splines: Vec<_> = particles
+            .iter()
+            .map(|particle| self.splines(particle.pos, boundary))
+            .collect();
+        let mut grid = vec![Complex::zero(); nx * ny * nz];
+        for (particle, [sx, sy, sz]) in particles.iter().zip(&splines) {
+            for &(x, wx, _) in sx {
+                for &(y, wy, _) in sy {
+                    for &(z, wz, _) in sz {
+                        grid[index(x, y, z)].re += particle.charge * wx * wy * wz;
+                    }
+                }
+            }
+        }
+
+        // Transform the grid, and multiply it by C(m) to convolve it with θ.
+        fft3(&mut grid, self.dims, Direction::Forward);
+        let moduli = [nx, ny, nz].map(|n| bspline_moduli(self.order, n));
+        let k_unit = Vec3::new(2.0 * PI, 2.0 * PI, 2.0 * PI) / boundary;
+        // Indices beyond half the grid correspond to negative frequencies.
+        let frequency = |m: usize, n: usize| {
+            if m <= n / 2 {
+                m as f64
+            } else {
+                m as f64 - n as f64
+            }
+        };
+        let mut energy = 0.0;
+        let mut virial = 0.0;
+        for mx in 0..nx {
+            for my in 0..ny {
+                for mz in 0..nz {
+                    let value = &mut grid[index(mx, my, mz)];
+                    if (mx, my, mz) == (0, 0, 0) {
+                        *value = Complex::zero();
+                        continue;
+                    }
+                    let k =
+                        Vec3::new(frequency(mx, nx), frequency(my, ny), frequency(mz, nz)) * k_unit;
+                    let k2 = k.x * k.x + k.y * k.y + k.z * k.z;
+                    let b2 = moduli[0][mx] * moduli[1][my] * moduli[2][mz];
+                    let c = COULOMB * 4.0 * PI / volume
+                        * (-k2 / (4.0 * self.alpha * self.alpha)).exp()
+                        / k2
+                        * b2;
+                    let e_m = 0.5 * c * value.norm_sqr();
+                    energy += e_m;
+                    virial += e_m * (1.0 - k2 / (2.0 * self.alpha * self.alpha));
+                    *value *= c;
+                }
+            }
+        }
+        fft3(&mut grid, self.dims, Direction::Inverse);
+
+        // F_i = -q_i * Σ_g ∇(w_x * w_y * w_z) * (θ ⋆ Q)(g)
+        let scale = Vec3::new(nx as f64, ny as f64, nz as f64) / boundary;
+        for ((particle, force), [sx, sy, sz]) in particles.iter().zip(forces).zip(&splines) {
+            let mut gradient = Vec3::zero();
+            for &(x, wx, dx) in sx {
+                for &(y, wy, dy) in sy {
+                    for &(z, wz, dz) in sz {
+                        let potential = grid[index(x, y, z)].re;
+                        gradient += Vec3::new(dx * wy * wz, wx * dy * wz, wx * wy * dz) * potential;
+                    }
+                }
+            }
+            *force -= gradient * scale * particle.charge;
+        }
+
+        (energy, virial)
+    }
+
+    /// Returns, for each axis, the grid points that a charge at some position is spread onto,
+    /// along with the B-spline weights and their derivatives to the scaled coordinate.
+    fn splines(&self, pos: Vec3, boundary: Vec3) -> [Vec<(usize, f64, f64)>; 3] {
+        // The scaled coordinate u runs from 0 to the number of grid points across the box.
+        let fractional = pos / boundary + Vec3::one() * 0.5;
+        let spline = |f: f64, n: usize| {
+            let u = f * n as f64;
+            let floor = u.floor();
+            let (weights, derivatives) = bspline_weights(self.order, u - floor);
+            // Weight j belongs to the grid point at floor(u) - j.
+            (0..self.order)
+                .map(|j| {
+                    let point = (floor as isize - j as isize).rem_euclid(n as isize) as usize;
+                    (point, weights[j], derivatives[j])
+                })
+                .collect()
+        };
+        [
+            spline(fractional.x, self.dims[0]),
+            spline(fractional.y, self.dims[1]),
+            spline(fractional.z, self.dims[2]),
+        ]
+    }
+}
+
+/// Returns the cardinal B-spline M_n(w + j) of order n and its derivative, for j in 0..n, and
+/// some 0 <= w < 1.
+///
+/// ```
+/// M_2(u) = 1 - |u - 1|   for 0 <= u <= 2
+/// M_n(u) = u / (n - 1) * M_{n - 1}(u) + (n - u) / (n - 1) * M_{n - 1}(u - 1)
+/// M_n'(u) = M_{n - 1}(u) - M_{n - 1}(u - 1)
+/// ```
+fn bspline_weights(order: usize, w: f64) -> (Vec<f64>, Vec<f64>) {
+    assert!(order >= 2, "order of B-splines must be at least 2");
+    let mut weights = vec![0.0; order];
+    weights[0] = w;
+    weights[1] = 1.0 - w;
+    let mut derivatives = vec![1.0, -1.0];
+    for n in 3..=order {
+        let previous = weights.clone();
+        for j in 0..n {
+            let u = w + j as f64;
+            let below = if j > 0 { previous[j - 1] } else { 0.0 };
+            weights[j] = (u * previous[j] + (n as f64 - u) * below) / (n - 1) as f64;
+        }
+        if n == order {
+            derivatives = (0..n)
+                .map(|j| previous[j] - if j > 0 { previous[j - 1] } else { 0.0 })
+                .collect();
+        }
+    }
+    derivatives.resize(order, 0.0);
+    (weights, derivatives)
+}
+
+/// Returns |b(m)|^2 for every m on an axis with `n` grid points.
+///
+/// ```
+/// |b(m)|^2 = 1 / |Σ_{k = 0}^{order - 2} M_order(k + 1) * exp(2 * π * i * m * k / n)|^2
+/// ```
+fn bspline_moduli(order: usize, n: usize) -> Vec<f64> {
+    // M_order at the integers 0, 1, ..., order - 1.
+    let (at_integers, _) = bspline_weights(order, 0.0);
+    let mut moduli: Vec<f64> = (0..n)
+        .map(|m| {
+            let sum = (0..order - 1).fold(Complex::zero(), |sum, k| {
+                let phase = 2.0 * PI * m as f64 * k as f64 / n as f64;
+                sum + Complex::from_phase(phase) * at_integers[k + 1]
+            });
+            sum.norm_sqr()
+        })
+        .collect();
+    // For odd orders, the sum vanishes at m = n / 2. Interpolate from the neighbours there.
+    for m in 0..n {
+        if moduli[m] < 1e-7 {
+            moduli[m] = 0.5 * (moduli[(m + n - 1) % n] + moduli[(m + 1) % n]);
+        }
+    }
+    moduli.into_iter().map(|modulus| 1.0 / modulus).collect()
+}
+
+/// Reaction-field electrostatics.
+///
+/// Beyond the cutoff, the system is treated as a continuum with some dielectric constant ε_rf,
+/// which is polarized by the charges within the cutoff.
+///
+/// ```
+/// V_rf(r) = k_e * q_i * q_j * (1 / r + k_rf * r^2 - c_rf)
+/// k_rf = (ε_rf - 1) / ((2 * ε_rf + 1) * r_c^3)
+/// c_rf = 1 / r_c + k_rf * r_c^2
+/// ```
+///
+/// Such that the potential is zero at the cutoff.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReactionField {
+    /// Cutoff in meters.
+    pub cutoff: f64,
+    /// Dielectric constant beyond the cutoff. An infinite value describes a conductor.
+    pub epsilon_rf: f64,
+}
+
+impl ReactionField {
+    /// Returns k_rf and c_rf.
+    fn constants(&self) -> (f64, f64) {
+        let ratio = if self.epsilon_rf.is_infinite() {
+            0.5
+        } else {
+            (self.epsilon_rf - 1.0) / (2.0 * self.epsilon_rf + 1.0)
+        };
+        let k_rf = ratio / self.cutoff.powi(3);
+        let c_rf = 1.0 / self.cutoff + k_rf * self.cutoff.powi(2);
+        (k_rf, c_rf)
+    }
+
+    /// Returns the energy (J) and magnitude of the force (N) of a pair.
+    ///
+    /// ```
+    /// F_rf(r) = k_e * q_i * q_j * (1 / r^2 - 2 * k_rf * r)
+    /// ```
+    #[inline]
+    fn pair(&self, qq: f64, r: f64) -> (f64, f64) {
+        let (k_rf, c_rf) = self.constants();
+        let energy = COULOMB * qq * (1.0 / r + k_rf * r * r - c_rf);
+        let force = COULOMB * qq * (1.0 / (r * r) - 2.0 * k_rf * r);
+        (energy, force)
+    }
+
+    /// Returns the energy (J) and magnitude of the force (N) of an excluded pair within the
+    /// cutoff, which is the pair interaction without the plain Coulomb part.
+    ///
+    /// ```
+    /// V_excl(r) = k_e * q_i * q_j * (k_rf * r^2 - c_rf)
+    /// ```
+    #[inline]
+    fn excluded(&self, qq: f64, r: f64) -> (f64, f64) {
+        if r >= self.cutoff {
+            return (0.0, 0.0);
+        }
+        let (k_rf, c_rf) = self.constants();
+        let energy = COULOMB * qq * (k_rf * r * r - c_rf);
+        let force = COULOMB * qq * (-2.0 * k_rf * r);
+        (energy, force)
+    }
+}
+
+/// Damped shifted-force Wolf summation (Fennell and Gezelter, 2006).
+///
+/// The Coulomb interaction is damped like the real-space part of the [`Ewald`] summation, and
+/// shifted such that both the energy and the force vanish at the cutoff.
+///
+/// ```
+/// V_dsf(r) = k_e * q_i * q_j * [ erfc(α * r) / r - erfc(α * r_c) / r_c + F_c * (r - r_c) ]
+/// F_c = erfc(α * r_c) / r_c^2 + 2 * α / sqrt(π) * exp(-α^2 * r_c^2) / r_c
+/// ```
+///
+/// Every charge also interacts with the neutralizing charge at the cutoff,
+///
+/// ```
+/// E_self = -k_e * (erfc(α * r_c) / (2 * r_c) + α / sqrt(π)) * Σ_i q_i^2
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wolf {
+    /// Cutoff in meters.
+    cutoff: f64,
+    /// Damping parameter (1 / meter).
+    alpha: f64,
+    /// erfc(α * r_c) / r_c
+    energy_at_cutoff: f64,
+    /// F_c
+    force_at_cutoff: f64,
+}
+
+impl Wolf {
+    /// Creates a new [`Wolf`] summation for some cutoff (meter) and damping parameter
+    /// (1 / meter).
+    pub fn new(cutoff: f64, alpha: f64) -> Self {
+        let mut wolf = Self {
+            cutoff,
+            alpha,
+            energy_at_cutoff: erfc(alpha * cutoff) / cutoff,
+            force_at_cutoff: 0.0,
+        };
+        wolf.force_at_cutoff = wolf.damped_force(cutoff);
+        wolf
+    }
+
+    /// Returns the (unscaled) force of the damped interaction at some distance.
+    ///
+    /// ```
+    /// erfc(α * r) / r^2 + 2 * α / sqrt(π) * exp(-α^2 * r^2) / r
+    /// ```
+    #[inline]
+    fn damped_force(&self, r: f64) -> f64 {
+        let alpha_r = self.alpha * r;
+        erfc(alpha_r) / (r * r) + 2.0 * self.alpha / PI.sqrt() * (-alpha_r * alpha_r).exp() / r
+    }
+
+    /// Returns the energy (J) and magnitude of the force (N) of a pair.
+    ///
+    /// ```
+    /// F_dsf(r) = k_e * q_i * q_j * [ erfc(α * r) / r^2 + 2 * α / sqrt(π) * exp(-α^2 * r^2) / r - F_c ]
+    /// ```
+    #[inline]
+    fn pair(&self, qq: f64, r: f64) -> (f64, f64) {
+        let alpha_r = self.alpha * r;
+        let erfc_over_r = erfc(alpha_r) / r;
+        let gaussian = 2.0 * self.alpha / PI.sqrt() * (-alpha_r * alpha_r).exp();
+        let energy = COULOMB
+            * qq
+            * (erfc_over_r - self.energy_at_cutoff + self.force_at_cutoff * (r - self.cutoff));
+        let force = COULOMB * qq * ((erfc_over_r + gaussian) / r - self.force_at_cutoff);
+        (energy, force)
+    }
+
+    /// Returns the self energy (J).
+    fn self_energy(&self, particles: &[Particle]) -> f64 {
+        let sum_q2: f64 = particles.iter().map(|p| p.charge * p.charge).sum();
+        -COULOMB * (0.5 * self.energy_at_cutoff + self.alpha / PI.sqrt()) * sum_q2
+    }
+}
+
+/// Returns the real-space Ewald energy (J) and magnitude of the force (N) of a pair.
+///
+/// ```
+/// F_real(r) = k_e * q_i * q_j * [ erfc(α * r) / r + 2 * α / sqrt(π) * exp(-α^2 * r^2) ] / r
+/// ```
+#[inline]
+fn real_space(alpha: f64, qq: f64, r: f64) -> (f64, f64) {
+    let alpha_r = alpha * r;
+    let energy = COULOMB * qq * erfc(alpha_r) / r;
+    let gaussian = 2.0 * alpha / PI.sqrt() * (-alpha_r * alpha_r).exp();
+    let force = (energy + COULOMB * qq * gaussian) / r;
+    (energy, force)
+}
+
+/// Returns the Ewald self energy (J).
+fn self_energy(alpha: f64, particles: &[Particle]) -> f64 {
+    let sum_q2: f64 = particles.iter().map(|p| p.charge * p.charge).sum();
+    -COULOMB * alpha / PI.sqrt() * sum_q2
+}
+
+/// Returns the splitting parameter α (1 / meter) for which erfc(α * r_c) equals some tolerance.
+pub fn splitting_parameter(cutoff: f64, tolerance: f64) -> f64 {
+    // erfc is monotonically decreasing, so we can bisect on α * r_c.
+    let (mut low, mut high) = (0.0, 10.0);
+    for _ in 0..100 {
+        let mid = 0.5 * (low + high);
+        if erfc(mid) > tolerance {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    0.5 * (low + high) / cutoff
+}
+
+/// Complementary error function.
+///
+/// For small arguments, the Taylor series of erf is used. For larger arguments, the continued
+/// fraction of erfc converges quickly and does not suffer from cancellation.
+pub fn erfc(x: f64) -> f64 {
+    if x < 0.0 {
+        return 2.0 - erfc(-x);
+    }
+    if x < 2.0 {
+        // erf(x) = 2 / sqrt(π) * Σ_n (-1)^n * x^(2n + 1) / (n! * (2n + 1))
+        let x2 = x * x;
+        let mut term = x;
+        let mut sum = x;
+        let mut n = 0.0;
+        while term.abs() > 1e-17 * sum.abs() {
+            n += 1.0;
+            term *= -x2 / n;
+            sum += term / (2.0 * n + 1.0);
+        }
+        1.0 - 2.0 / PI.sqrt() * sum
+    } else {
+        // erfc(x) = exp(-x^2) / sqrt(π) * 1 / (x + 1/2 / (x + 1 / (x + 3/2 / (x + 2 / ...))))
+        let mut fraction = x;
+        for n in (1..=60).rev() {
+            fraction = x + 0.5 * n as f64 / fraction;
+        }
+        (-x * x).exp() / PI.sqrt() / fraction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::potential::{LennardJones, PairTable};
+    use crate::time::Time;
+    use crate::universe::Universe;
+
+    /// Madelung constant of the rock salt structure, with respect to the nearest-neighbour
+    /// distance.
+    const MADELUNG_NACL: f64 = 1.747564594633;
+
+    #[test]
+    fn erfc_matches_known_values() {
+        for (x, expected) in [
+            (0.0, 1.0),
+            (0.5, 0.4795001221869535),
+            (1.0, 0.15729920705028513),
+            (2.0, 0.004677734981047266),
+            (3.0, 2.209049699858544e-5),
+            (5.0, 1.537459794428035e-12),
+        ] {
+            let relative_error = (erfc(x) - expected).abs() / expected;
+            assert!(relative_error < 1e-12, "erfc({x}) = {}", erfc(x));
+        }
+    }
+
+    #[test]
+    fn ewald_reproduces_madelung_constant() {
+        // A rock salt lattice is a simple cubic lattice with alternating charges.
+        let n = 8;
+        let d = 0.282e-9;
+        let length = n as f64 * d;
+        let mut particles = Vec::new();
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    let pos = Vec3::new(x as f64, y as f64, z as f64) * d;
+                    let sign = if (x + y + z) % 2 == 0 { 1.0 } else { -1.0 };
+                    particles.push(
+                        Particle::new(pos, Vec3::zero(), Vec3::zero(), 1e-25)
+                            .charge(sign * ELEMENTARY_CHARGE),
+                    );
+                }
+            }
+        }
+        let boundary = Vec3::one() * length;
+        let cutoff = 0.5 * length;
+        let mut u = Universe::new(Time::from_femtoseconds(1.0), boundary, 300.0)
+            .cutoff(cutoff)
+            .potentials(PairTable::single(LennardJones::new(0.0, 0.3e-9)))
+            .electrostatics(Electrostatics::Ewald(Ewald::from_tolerance(
+                cutoff, boundary, 1e-8,
+            )))
+            .add_particles(&particles);
+        let forces = u.compute_forces();
+
+        // E = -M * k_e * e^2 * N / (2 * d)
+        let n_ions = particles.len() as f64;
+        let madelung =
+            -u.potential_energy() * 2.0 * d / (n_ions * COULOMB * ELEMENTARY_CHARGE.powi(2));
+        assert!(
+            (madelung - MADELUNG_NACL).abs() < 1e-6,
+            "Madelung constant is {madelung}"
+        );
+
+        // By symmetry, there is no net force on any of the ions.
+        let unit_force = COULOMB * ELEMENTARY_CHARGE.powi(2) / (d * d);
+        for force in forces {
+            assert!(force.norm() < 1e-6 * unit_force, "force is {force:?}");
+        }
+    }
+
+    /// Returns a neutral box of randomly placed ions, with the reciprocal-space forces and energy
+    /// of some summation.
+    fn random_ions(electrostatics: &Electrostatics, boundary: Vec3) -> (Vec<Vec3>, f64) {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let particles: Vec<_> = (0..100)
+            .map(|index| {
+                let pos = Vec3::new(
+                    rng.gen_range(-0.5..0.5),
+                    rng.gen_range(-0.5..0.5),
+                    rng.gen_range(-0.5..0.5),
+                ) * boundary;
+                let sign = if index % 2 == 0 { 1.0 } else { -1.0 };
+                Particle::new(pos, Vec3::zero(), Vec3::zero(), 1e-25)
+                    .charge(sign * ELEMENTARY_CHARGE)
+            })
+            .collect();
+        let mut forces = vec![Vec3::zero(); particles.len()];
+        let (energy, _) = electrostatics.long_range(&particles, boundary, &mut forces);
+        (forces, energy)
+    }
+
+    #[test]
+    fn pme_reproduces_ewald() {
+        let boundary = Vec3::new(2.0e-9, 2.2e-9, 2.4e-9);
+        let cutoff = 0.9e-9;
+        let tolerance = 1e-5;
+        let alpha = splitting_parameter(cutoff, tolerance);
+        // The real-space part is the same for both, so only the long-range parts are compared.
+        // The plain Ewald sum includes far more reciprocal lattice vectors than needed, so it can
+        // serve as the reference.
+        let ewald = Electrostatics::Ewald(Ewald {
+            alpha,
+            kmax: [16, 16, 16],
+        });
+        let pme = Electrostatics::Pme(Pme::new(cutoff, boundary, tolerance, 0.1e-9, 6));
+        let (ewald_forces, ewald_energy) = random_ions(&ewald, boundary);
+        let (pme_forces, pme_energy) = random_ions(&pme, boundary);
+
+        // With a grid spacing of 0.1 nm and splines of order 6, PME should reproduce the energy
+        // within a relative tolerance of 1e-5.
+        let relative_error = ((pme_energy - ewald_energy) / ewald_energy).abs();
+        assert!(relative_error < 1e-5, "relative error is {relative_error}");
+
+        // And the forces within 1e-3 of the typical force.
+        let typical_force = ewald_forces.iter().map(|f| f.norm()).sum::<f64>() / 100.0;
+        for (ewald_force, pme_force) in ewald_forces.iter().zip(&pme_forces) {
+            let error = (*pme_force - *ewald_force).norm() / typical_force;
+            assert!(error < 1e-3, "relative force error is {error}");
+        }
+    }
+
+    #[test]
+    fn pme_grid_is_a_power_of_two_for_an_odd_order() {
+        // A 2 nm box is 4 spacings of 0.5 nm wide, which is fewer points than the order.
+        let boundary = Vec3::one() * 2.0e-9;
+        let pme = Pme::new(0.9e-9, boundary, 1e-5, 0.5e-9, 5);
+        assert_eq!(pme.dims, [8, 8, 8]);
+        let (forces, energy) = random_ions(&Electrostatics::Pme(pme), boundary);
+        assert!(energy.is_finite());
+        assert!(forces.iter().all(|f| f.norm().is_finite()));
+    }
+
+    #[test]
+    fn reaction_field_applies_to_excluded_pairs() {
+        let electrostatics = Electrostatics::ReactionField(ReactionField {
+            cutoff: 1.0e-9,
+            epsilon_rf: 78.0,
+        });
+        let qq = -0.8476 * 0.4238 * ELEMENTARY_CHARGE.powi(2);
+        for r in [0.1e-9, 0.16e-9, 0.5e-9] {
+            // Together with the plain Coulomb interaction that is excluded, an excluded pair
+            // interacts as any other pair.
+            let (energy, force) = electrostatics.excluded(qq, r);
+            let (pair_energy, pair_force) = electrostatics.pair(qq, r);
+            let coulomb = COULOMB * qq / r;
+            assert!((energy + coulomb - pair_energy).abs() < 1e-12 * coulomb.abs());
+            assert!((force + coulomb / r - pair_force).abs() < 1e-12 * (coulomb / r).abs());
+        }
+        assert_eq!(electrostatics.excluded(qq, 1.2e-9), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,176 @@
+use std::f64::consts::PI;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub const fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    pub const fn zero() -> Self {
+        Self::new(0.0, 0.0)
+    }
+
+    /// Returns exp(i * phase).
+    pub fn from_phase(phase: f64) -> Self {
+        Self::new(phase.cos(), phase.sin())
+    }
+
+    /// Square of the magnitude.
+    pub fn norm_sqr(&self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl AddAssign for Complex {
+    fn add_assign(&mut self, rhs: Self) {
+        self.re += rhs.re;
+        self.im += rhs.im;
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Mul<f64> for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self::Output {
+        Self::new(self.re * rhs, self.im * rhs)
+    }
+}
+
+impl MulAssign<f64> for Complex {
+    fn mul_assign(&mut self, rhs: f64) {
+        self.re *= rhs;
+        self.im *= rhs;
+    }
+}
+
+/// Direction of a Fourier transform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// X(m) = Σ_k x(k) * exp(-2 * π * i * m * k / N)
+    Forward,
+    /// x(k) = Σ_m X(m) * exp(2 * π * i * m * k / N)
+    ///
+    /// Note that the result is not divided by N.
+    Inverse,
+}
+
+/// In-place radix-2 fast Fourier transform.
+///
+/// The length of `data` must be a power of two.
+pub fn fft(data: &mut [Complex], direction: Direction) {
+    let twiddles = twiddles(data.len(), direction);
+    fft_rows(data, 1, &twiddles);
+}
+
+/// Returns the twiddle factors exp(∓ 2 * π * i * k / n) for k in 0..n / 2.
+fn twiddles(n: usize, direction: Direction) -> Vec<Complex> {
+    let sign = match direction {
+        Direction::Forward => -1.0,
+        Direction::Inverse => 1.0,
+    };
+    (0..n / 2)
+        .map(|k| Complex::from_phase(sign * 2.0 * PI * k as f64 / n as f64))
+        .collect()
+}
+
+/// In-place radix-2 fast Fourier transform of the rows of `data`, which consists of some number
+/// of rows of `row` elements each.
+///
+/// In other words, the element at index `i * row + j` is the `i`th element of the `j`th sequence
+/// to transform. Since every butterfly operates on whole rows, the memory is accessed
+/// contiguously, no matter how long the rows are. The number of rows must be a power of two, and
+/// `twiddles` must be the twiddle factors for that number.
+fn fft_rows(data: &mut [Complex], row: usize, twiddles: &[Complex]) {
+    let n = data.len() / row;
+    assert!(n.is_power_of_two(), "length of fft must be a power of two");
+    if n <= 1 {
+        return;
+    }
+
+    // Put the rows in bit-reversed order.
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            let (head, tail) = data.split_at_mut(j * row);
+            head[i * row..(i + 1) * row].swap_with_slice(&mut tail[..row]);
+        }
+    }
+
+    // Combine the transforms of ever longer blocks. The twiddle factors for a block of length
+    // len are every (n / len)th of those for n.
+    let mut len = 2;
+    while len <= n {
+        let half = len / 2;
+        let step = n / len;
+        for block in data.chunks_exact_mut(len * row) {
+            let (even, odd) = block.split_at_mut(half * row);
+            for k in 0..half {
+                let w = twiddles[k * step];
+                let even = &mut even[k * row..(k + 1) * row];
+                let odd = &mut odd[k * row..(k + 1) * row];
+                for (a, b) in even.iter_mut().zip(odd.iter_mut()) {
+                    let t = w * *b;
+                    *b = *a - t;
+                    *a += t;
+                }
+            }
+        }
+        len *= 2;
+    }
+}
+
+/// In-place three-dimensional fast Fourier transform.
+///
+/// The `data` is laid out such that the element at (x, y, z) is at index
+/// `(x * dims[1] + y) * dims[2] + z`. Every dimension must be a power of two.
+pub fn fft3(data: &mut [Complex], dims: [usize; 3], direction: Direction) {
+    let [nx, ny, nz] = dims;
+    assert_eq!(data.len(), nx * ny * nz);
+
+    // Along z, every line is a separate transform.
+    let twiddles_z = twiddles(nz, direction);
+    for line in data.chunks_exact_mut(nz) {
+        fft_rows(line, 1, &twiddles_z);
+    }
+    // Along y, every plane at some x is a transform of rows of nz elements.
+    let twiddles_y = twiddles(ny, direction);
+    for plane in data.chunks_exact_mut(ny * nz) {
+        fft_rows(plane, nz, &twiddles_y);
+    }
+    // Along x, the whole grid is a transform of rows of ny * nz elements.
+    fft_rows(data, ny * nz, &twiddles(nx, direction));
+}
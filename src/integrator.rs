@@ -0,0 +1,404 @@
+use rand::{rngs::StdRng, Rng};
+
+use crate::universe::{Particle, BOLTZMANN};
+use crate::vec3::Vec3;
+
+/// Friction coefficient of Langevin dynamics if none is given (1/s).
+pub const DEFAULT_FRICTION: f64 = 1e12;
+
+/// Scheme to advance the positions and velocities of the particles over a time step.
+///
+/// A step is split around the evaluation of the forces. First the positions are moved from the
+/// start to the end of the step, with the accelerations at its start. Then, once the forces at
+/// the end of the step are known, the velocities are completed. The constraints are applied
+/// after each of the two stages.
+pub trait Integrator: std::fmt::Debug {
+    /// Moves the particles over a time step `dt` (seconds), with the accelerations at the start of
+    /// the step.
+    fn update_positions(&mut self, particles: &mut [Particle], dt: f64);
+
+    /// Completes the velocities at the end of a time step `dt` (seconds), with the new
+    /// accelerations.
+    fn update_velocities(&mut self, particles: &mut [Particle], dt: f64);
+
+    /// Whether the velocities are kept half a step behind the positions.
+    fn half_step_velocities(&self) -> bool {
+        false
+    }
+
+    /// Whether the total momentum of the particles is conserved, such that the motion of the
+    /// centre of mass does not count towards the degrees of freedom.
+    fn conserves_momentum(&self) -> bool {
+        true
+    }
+}
+
+/// Velocity Verlet, which gives the velocities a half kick with the old and a half kick with the
+/// new forces around a drift of the positions.
+///
+/// ```
+/// v(t + Δt/2) = v(t) + 1/2 * a(t) * Δt
+/// r(t + Δt)   = r(t) + v(t + Δt/2) * Δt
+/// v(t + Δt)   = v(t + Δt/2) + 1/2 * a(t + Δt) * Δt
+/// ```
+///
+/// It is time-reversible and symplectic, so the energy is conserved without drift.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VelocityVerlet;
+
+impl Integrator for VelocityVerlet {
+    fn update_positions(&mut self, particles: &mut [Particle], dt: f64) {
+        for particle in particles {
+            particle.vel += particle.acc * (0.5 * dt);
+            particle.pos += particle.vel * dt;
+        }
+    }
+
+    fn update_velocities(&mut self, particles: &mut [Particle], dt: f64) {
+        for particle in particles {
+            particle.vel += particle.acc * (0.5 * dt);
+        }
+    }
+}
+
+/// Leap-frog, as in GROMACS, which keeps the velocities half a step behind the positions.
+///
+/// ```
+/// v(t + Δt/2) = v(t - Δt/2) + a(t) * Δt
+/// r(t + Δt)   = r(t) + v(t + Δt/2) * Δt
+/// ```
+///
+/// The positions are the same as those of velocity Verlet. The velocities of the start
+/// configuration are taken to be at -Δt/2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LeapFrog;
+
+impl Integrator for LeapFrog {
+    fn update_positions(&mut self, particles: &mut [Particle], dt: f64) {
+        for particle in particles {
+            particle.vel += particle.acc * dt;
+            particle.pos += particle.vel * dt;
+        }
+    }
+
+    fn update_velocities(&mut self, _particles: &mut [Particle], _dt: f64) {}
+
+    fn half_step_velocities(&self) -> bool {
+        true
+    }
+}
+
+/// Langevin dynamics with the BAOAB splitting of Leimkuhler and Matthews, which samples the
+/// canonical ensemble at the temperature of its heat bath.
+///
+/// Each step the velocities get a half kick (B), the positions a half drift (A), then the
+/// velocities are partly replaced by random ones from the heat bath (O), after which follow another
+/// half drift and, with the new forces, another half kick.
+///
+/// ```
+/// v' = exp(-γ * Δt) * v + sqrt((1 - exp(-2 * γ * Δt)) * k_B * T / m) * R
+/// ```
+///
+/// where γ is the friction coefficient and R a standard normal random number for each component.
+///
+/// See Leimkuhler, B. & Matthews, C. (2013). Appl. Math. Res. Express 2013, 34.
+#[derive(Debug, Clone)]
+pub struct Langevin {
+    /// Friction coefficient γ (1/s).
+    pub friction: f64,
+    /// Temperature of the heat bath (Kelvin).
+    pub temperature: f64,
+    rng: StdRng,
+}
+
+impl Langevin {
+    /// Creates Langevin dynamics that draws its random forces from `rng`, such that runs from the
+    /// same seed are the same.
+    pub fn new(friction: f64, temperature: f64, rng: StdRng) -> Self {
+        Self {
+            friction,
+            temperature,
+            rng,
+        }
+    }
+}
+
+impl Integrator for Langevin {
+    fn update_positions(&mut self, particles: &mut [Particle], dt: f64) {
+        let damping = (-self.friction * dt).exp();
+        let kt = BOLTZMANN * self.temperature;
+        for particle in particles {
+            particle.vel += particle.acc * (0.5 * dt);
+            particle.pos += particle.vel * (0.5 * dt);
+            let sigma = ((1.0 - damping * damping) * kt / particle.mass).sqrt();
+            particle.vel = particle.vel * damping + gaussian_vec3(&mut self.rng) * sigma;
+            particle.pos += particle.vel * (0.5 * dt);
+        }
+    }
+
+    fn update_velocities(&mut self, particles: &mut [Particle], dt: f64) {
+        for particle in particles {
+            particle.vel += particle.acc * (0.5 * dt);
+        }
+    }
+
+    fn conserves_momentum(&self) -> bool {
+        // The random forces kick the centre of mass around.
+        false
+    }
+}
+
+/// Overdamped Brownian dynamics, in which the particles have no inertia and move with the force
+/// and random displacements from the solvent, as for colloids.
+///
+/// ```
+/// r(t + Δt) = r(t) + D / (k_B * T) * F(t) * Δt + sqrt(2 * D * Δt) * R
+/// ```
+///
+/// where D is the diffusion coefficient of every particle, and R a standard normal random number
+/// for each component. The particles have no velocities, so those stored are zero.
+#[derive(Debug, Clone)]
+pub struct Brownian {
+    /// Diffusion coefficient D (m^2/s).
+    pub diffusion: f64,
+    /// Temperature of the solvent (Kelvin).
+    pub temperature: f64,
+    rng: StdRng,
+}
+
+impl Brownian {
+    /// Creates Brownian dynamics that draws its random displacements from `rng`, such that runs
+    /// from the same seed are the same.
+    pub fn new(diffusion: f64, temperature: f64, rng: StdRng) -> Self {
+        Self {
+            diffusion,
+            temperature,
+            rng,
+        }
+    }
+}
+
+impl Integrator for Brownian {
+    fn update_positions(&mut self, particles: &mut [Particle], dt: f64) {
+        let mobility = self.diffusion / (BOLTZMANN * self.temperature);
+        let sigma = (2.0 * self.diffusion * dt).sqrt();
+        for particle in particles {
+            let force = particle.acc * particle.mass;
+            particle.pos += force * (mobility * dt) + gaussian_vec3(&mut self.rng) * sigma;
+        }
+    }
+
+    fn update_velocities(&mut self, particles: &mut [Particle], _dt: f64) {
+        // The constraints may have given the particles some velocity.
+        for particle in particles {
+            particle.vel = Vec3::zero();
+        }
+    }
+
+    fn conserves_momentum(&self) -> bool {
+        // The random displacements move the centre of mass.
+        false
+    }
+}
+
+/// Returns a vector of standard normal random numbers, drawn with the Box-Muller transform.
+pub(crate) fn gaussian_vec3(rng: &mut impl Rng) -> Vec3 {
+    let mut gaussian = || {
+        // Draw from (0, 1], such that the logarithm is finite.
+        let (u, v): (f64, f64) = (1.0 - rng.gen::<f64>(), rng.gen());
+        (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos()
+    };
+    Vec3::new(gaussian(), gaussian(), gaussian())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::potential::{LennardJones, Modifier, PairTable, AVOGADRO};
+    use crate::time::Time;
+    use crate::topology::{Bond, Topology};
+    use crate::universe::{minimum_image, Thermostat, Universe, BOLTZMANN};
+    use crate::vec3::Vec3;
+
+    /// Returns an argon-like fluid, advanced by some integrator.
+    fn argon(integrator: Box<dyn Integrator>) -> Universe {
+        let mut u = crate::universe::tests::argon(Modifier::ForceShift).integrator(integrator);
+        u.dt = Time::from_femtoseconds(5.0);
+        u
+    }
+
+    #[test]
+    fn leap_frog_follows_velocity_verlet() {
+        let mut verlet = argon(Box::new(VelocityVerlet));
+        verlet.prepare().unwrap();
+        // Leap-frog starts from the velocities half a step earlier.
+        let mut leap_frog = argon(Box::new(LeapFrog));
+        leap_frog.prepare().unwrap();
+        let dt = leap_frog.dt.seconds();
+        for particle in &mut leap_frog.particles {
+            particle.vel -= particle.acc * (0.5 * dt);
+        }
+
+        for _ in 0..200 {
+            verlet.step().unwrap();
+            leap_frog.step().unwrap();
+        }
+        let scale = 1e-11 * verlet.boundary.x;
+        for (a, b) in verlet.particles.iter().zip(&leap_frog.particles) {
+            assert!((a.pos - b.pos).norm() < scale, "{:?} {:?}", a.pos, b.pos);
+            let synchronized = leap_frog.synchronized_velocity(b);
+            assert!((a.vel - synchronized).norm() < 1e-9 * a.vel.norm().max(1.0));
+            // The stored velocities are half a step behind.
+            assert!((a.vel - b.vel).norm() > 1e-6 * a.vel.norm());
+        }
+        let (a, b) = (verlet.kinetic_energy(), leap_frog.kinetic_energy());
+        assert!((a - b).abs() < 1e-9 * a);
+    }
+
+    #[test]
+    fn langevin_samples_the_canonical_ensemble() {
+        let temperature = 120.0;
+        let langevin = Langevin::new(1e13, temperature, StdRng::seed_from_u64(7));
+        let mut u = argon(Box::new(langevin));
+        u.prepare().unwrap();
+        u.steps(1000).unwrap();
+
+        // Without constraints or a fixed centre of mass, each particle has 3 degrees of freedom.
+        // In the canonical ensemble, the kinetic energy fluctuates by sqrt(2 / (3 N)) of its mean.
+        let degrees_of_freedom = 3.0 * u.particles.len() as f64;
+        let steps = 8000;
+        let (mut mean, mut square) = (0.0, 0.0);
+        for _ in 0..steps {
+            u.step().unwrap();
+            let kinetic = u.kinetic_energy();
+            mean += kinetic / steps as f64;
+            square += kinetic * kinetic / steps as f64;
+        }
+        let current = 2.0 * mean / (degrees_of_freedom * BOLTZMANN);
+        let fluctuation = (square - mean * mean).sqrt() / mean;
+        let expected = (2.0 / degrees_of_freedom).sqrt();
+        assert!(
+            (current - temperature).abs() < 0.02 * temperature,
+            "{current}"
+        );
+        assert!(
+            (fluctuation - expected).abs() < 0.1 * expected,
+            "{fluctuation} {expected}"
+        );
+    }
+
+    #[test]
+    fn langevin_is_reproducible_from_its_seed() {
+        let run = |seed: u64| {
+            let mut u = argon(Box::new(Langevin::new(
+                1e12,
+                120.0,
+                StdRng::seed_from_u64(seed),
+            )));
+            u.prepare().unwrap();
+            u.steps(50).unwrap();
+            u.particles.iter().map(|p| p.pos).collect::<Vec<_>>()
+        };
+        assert_eq!(run(5), run(5));
+        assert_ne!(run(5), run(6));
+    }
+
+    /// Returns particles at random positions in a box, which do not interact with each other, with
+    /// a second particle bonded to each at some distance if given.
+    fn solution(n: usize, bonded: Option<f64>, brownian: Brownian, dt: Time) -> Universe {
+        let mut rng = StdRng::seed_from_u64(23);
+        let length = 10e-9;
+        let mut random = |scale: f64| Vec3::new(rng.gen(), rng.gen(), rng.gen()) * scale;
+        let mut particles = Vec::new();
+        let mut topology = Topology::new();
+        let mass = 1e-24;
+        for _ in 0..n {
+            let pos = random(length) - Vec3::one() * (0.5 * length);
+            particles.push(Particle::new(pos, Vec3::zero(), Vec3::zero(), mass));
+            if let Some(r0) = bonded {
+                let direction = random(1.0) - Vec3::one() * 0.5;
+                let pos = pos + direction * (r0 / direction.norm());
+                particles.push(Particle::new(pos, Vec3::zero(), Vec3::zero(), mass));
+                let i = particles.len() - 2;
+                let k = 1000.0 * 1e3 / AVOGADRO / 1e-18;
+                topology.add_bond(Bond {
+                    particles: (i, i + 1),
+                    k,
+                    r0,
+                });
+            }
+        }
+        let temperature = brownian.temperature;
+        let mut u = Universe::new(dt, Vec3::one() * length, temperature)
+            .potentials(PairTable::single(LennardJones::from_molar(0.0, 0.3e-9)))
+            .topology(topology)
+            .thermostat(Thermostat::None)
+            .integrator(Box::new(brownian))
+            .add_particles(&particles);
+        u.prepare().unwrap();
+        u
+    }
+
+    #[test]
+    fn brownian_dynamics_recovers_the_diffusion_coefficient() {
+        // About that of a small molecule in water, 1 nm^2/ns.
+        let diffusion = 1e-9;
+        let brownian = Brownian::new(diffusion, 300.0, StdRng::seed_from_u64(5));
+        let dt = Time::from_picoseconds(1.0);
+        let mut u = solution(1000, None, brownian, dt);
+
+        // Follow the particles across the periodic boundaries. Their mean squared displacement
+        // grows as 6 * D * t, which is averaged over consecutive windows of time.
+        let (windows, steps) = (5, 100);
+        let mut msd = 0.0;
+        for _ in 0..windows {
+            let mut displacements = vec![Vec3::zero(); u.particles.len()];
+            for _ in 0..steps {
+                let before: Vec<Vec3> = u.particles.iter().map(|p| p.pos).collect();
+                u.step().unwrap();
+                for ((displacement, particle), pos) in
+                    displacements.iter_mut().zip(&u.particles).zip(before)
+                {
+                    *displacement += minimum_image(particle.pos - pos, u.boundary);
+                }
+            }
+            msd += displacements.iter().map(|d| d.norm().powi(2)).sum::<f64>()
+                / (windows * displacements.len()) as f64;
+        }
+        let recovered = msd / (6.0 * steps as f64 * dt.seconds());
+        assert!(
+            (recovered - diffusion).abs() < 0.05 * diffusion,
+            "{recovered}"
+        );
+        assert_eq!(u.kinetic_energy(), 0.0);
+    }
+
+    #[test]
+    fn brownian_dynamics_samples_the_boltzmann_distribution() {
+        // The bond length fluctuates by sqrt(k_B * T / k) around its equilibrium length, which is
+        // only right if the drift by the forces balances the random displacements.
+        let (temperature, r0) = (300.0, 1e-9);
+        let brownian = Brownian::new(1e-9, temperature, StdRng::seed_from_u64(47));
+        let mut u = solution(200, Some(r0), brownian, Time::from_femtoseconds(20.0));
+        u.steps(500).unwrap();
+
+        let bonds = u.topology.bonds.clone();
+        let mut variance = 0.0;
+        let samples = 150;
+        for _ in 0..samples {
+            u.steps(10).unwrap();
+            for bond in &bonds {
+                let (i, j) = bond.particles;
+                let r = minimum_image(u.particles[i].pos - u.particles[j].pos, u.boundary).norm();
+                variance += (r - r0).powi(2) / (samples * bonds.len()) as f64;
+            }
+        }
+        let expected = BOLTZMANN * temperature / bonds[0].k;
+        assert!(
+            (variance - expected).abs() < 0.05 * expected,
+            "{variance} {expected}"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use std::fs::read_to_string;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use constraints::Settle;
+use integrator::{Brownian, Integrator, Langevin, LeapFrog, VelocityVerlet};
+use minimize::Minimizer;
+use potential::AVOGADRO;
+use recipe::{IntegratorSetting, MinimizerSetting, Recipe};
+use species::lennard_jones_table;
+use topology::Topology;
+use trajectory::Trajectory;
+use universe::{Particle, Thermostat, Universe};
+use vec3::Vec3;
+
+pub mod constraints;
+pub mod electrostatics;
+pub mod fft;
+pub mod integrator;
+pub mod minimize;
+pub mod neighbours;
+pub mod potential;
+pub mod recipe;
+pub mod species;
+pub mod thermostat;
+pub mod time;
+pub mod topology;
+pub mod trajectory;
+pub mod universe;
+pub mod vec3;
+pub mod water;
+
+/// Runs the simulation of the `recipe.bibber` file in the current working directory, and writes
+/// its trajectory to standard out.
+pub fn run() {
+    // Read our recipe file. This is the configuration of the system.
+    let recipe = Recipe::from_string(read_to_string("recipe.bibber").unwrap()).unwrap();
+
+    // Prepare some particles is a totally not hacky way.
+    let boundary = recipe.boundary;
+    let mut rng = StdRng::seed_from_u64(20384093824);
+    let mut gen_in_range = |bound: f64| rng.gen_range(-0.5 * bound..0.5 * bound);
+    let mut gen_particle = |mass: f64| {
+        Particle::new(
+            Vec3::new(
+                gen_in_range(boundary.x),
+                gen_in_range(boundary.y),
+                gen_in_range(boundary.z),
+            ),
+            Vec3::new(
+                gen_in_range(boundary.x * 100.0),
+                gen_in_range(boundary.y * 100.0),
+                gen_in_range(boundary.z * 100.0),
+            ),
+            Vec3::zero(),
+            mass,
+        )
+    };
+    let n_particles = recipe.n_particles();
+    let mut particles: Vec<Particle> = Vec::with_capacity(n_particles);
+    let mut pruned = 0;
+    // Particles that are generated (almost) on top of another one are drawn again, apart from the
+    // particle they are placed next to, if any.
+    let overlaps = |particles: &[Particle], candidate: &Particle, anchor: Option<usize>| {
+        (particles.iter().enumerate())
+            .filter(|&(index, _)| Some(index) != anchor)
+            .any(|(_, particle)| (particle.pos - candidate.pos).norm() < 1e-10)
+    };
+    let species_indices = recipe
+        .particles
+        .iter()
+        .enumerate()
+        .flat_map(|(index, &count)| std::iter::repeat_n(index, count));
+    for species_index in species_indices {
+        let species = &recipe.species[species_index];
+        loop {
+            let candidate = gen_particle(species.mass)
+                .species(species_index)
+                .charge(species.charge);
+            if overlaps(&particles, &candidate, None) {
+                pruned += 1;
+                continue;
+            }
+
+            particles.push(candidate);
+            break;
+        }
+    }
+    // The molecules start at the sites of a simple cubic lattice, such that they do not overlap.
+    // The particles of a molecule are placed one after the other, each at its bond length from
+    // a particle it is bonded (or constrained) to, in the direction of its (random) velocity.
+    // A particle that lands on another one is placed again, in another direction. The first
+    // particle of a molecule moves off its site in a random direction if the site is taken.
+    let n_molecules: usize = recipe.molecule_counts.iter().sum();
+    let per_edge = (n_molecules as f64).cbrt().ceil() as usize;
+    let site = |index: usize| {
+        let coordinate = |i: usize| (i as f64 + 0.5) / per_edge as f64 - 0.5;
+        let (x, y, z) = (
+            index % per_edge,
+            index / per_edge % per_edge,
+            index / per_edge.pow(2),
+        );
+        Vec3::new(coordinate(x), coordinate(y), coordinate(z)) * boundary
+    };
+    let mut topology = Topology::new();
+    let mut sites = 0..n_molecules;
+    for (molecule, &count) in recipe.molecules.iter().zip(&recipe.molecule_counts) {
+        for site_index in sites.by_ref().take(count) {
+            let offset = particles.len();
+            for (index, &species_index) in molecule.species.iter().enumerate() {
+                let species = &recipe.species[species_index];
+                let mut attempts = 0;
+                let particle = loop {
+                    let mut particle = gen_particle(species.mass)
+                        .species(species_index)
+                        .charge(species.charge);
+                    let direction = particle.vel / particle.vel.norm();
+                    let anchor = if index == 0 {
+                        particle.pos = site(site_index) + direction * (1e-10 * attempts as f64);
+                        None
+                    } else {
+                        let bonds = molecule.bonds.iter().map(|b| (b.particles, b.r0));
+                        let settles = molecule.settles.iter().flat_map(Settle::constraints);
+                        let constraints = (molecule.constraints.iter().copied())
+                            .chain(settles)
+                            .map(|c| (c.particles, c.length));
+                        let (anchor, length) = bonds
+                            .chain(constraints)
+                            .find_map(|(particles, length)| match particles {
+                                (i, j) if i == index && j < index => Some((j, length)),
+                                (i, j) if j == index && i < index => Some((i, length)),
+                                _ => None,
+                            })
+                            .unwrap_or((index - 1, species.sigma));
+                        particle.pos = particles[offset + anchor].pos + direction * length;
+                        Some(offset + anchor)
+                    };
+                    if !overlaps(&particles, &particle, anchor) {
+                        break particle;
+                    }
+                    pruned += 1;
+                    attempts += 1;
+                };
+                particles.push(particle);
+            }
+            topology.add_molecule(molecule, offset);
+        }
+    }
+    eprintln!("Pruned {pruned} particles to get {n_particles}.");
+
+    // Langevin and Brownian dynamics keep the temperature themselves, with random forces from the
+    // same seeded generator, such that runs are reproducible.
+    let (integrator, thermostat): (Box<dyn Integrator>, _) = match recipe.integrator {
+        IntegratorSetting::Verlet => (Box::new(VelocityVerlet), recipe.thermostat),
+        IntegratorSetting::LeapFrog => (Box::new(LeapFrog), recipe.thermostat),
+        IntegratorSetting::Langevin { friction } => (
+            Box::new(Langevin::new(friction, recipe.temperature, rng)),
+            Thermostat::None,
+        ),
+        IntegratorSetting::Brownian { diffusion } => (
+            Box::new(Brownian::new(diffusion, recipe.temperature, rng)),
+            Thermostat::None,
+        ),
+    };
+
+    // Create the universe :)
+    let mut u = Universe::new(recipe.timestep, recipe.boundary, recipe.temperature)
+        .start(recipe.start)
+        .cutoff(recipe.cutoff)
+        .skin(recipe.skin)
+        .rebuild_interval(recipe.rebuild_interval)
+        .potentials(
+            lennard_jones_table(&recipe.species, recipe.mixing, &recipe.pairs)
+                .truncated(recipe.cutoff, recipe.modifier),
+        )
+        .electrostatics(recipe.electrostatics.clone())
+        .dispersion_correction(recipe.dispersion_correction)
+        .topology(topology)
+        .fudge_factors(recipe.fudge_lj, recipe.fudge_coulomb)
+        .constraint_solver(recipe.constraint_solver.clone())
+        .integrator(integrator)
+        .thermostat(thermostat)
+        .add_particles(&particles);
+
+    // The molecules are not generated in their constrained shapes, and the first step starts from
+    // the forces on the start configuration.
+    if let Err(error) = u.prepare() {
+        eprintln!("error in the start configuration: {error}");
+        std::process::exit(1);
+    }
+
+    // Report energies in kJ/mol, and forces in kJ/mol/nm.
+    let to_kj_per_mol = |energy: f64| energy * AVOGADRO * 1e-3;
+
+    // The drift of the energy of the extended system measures the error of the integration.
+    let nose_hoover = matches!(u.thermostat, Thermostat::NoseHoover { .. });
+    let conserved_start = u.conserved_energy();
+
+    // Minimize the energy instead of running dynamics, and write the minimized structure.
+    if let Some(setting) = recipe.minimize {
+        let minimizer: Box<dyn Minimizer> = match setting {
+            MinimizerSetting::SteepestDescent(minimizer) => Box::new(minimizer),
+            MinimizerSetting::ConjugateGradient(minimizer) => Box::new(minimizer),
+            MinimizerSetting::Lbfgs(minimizer) => Box::new(minimizer),
+        };
+        let minimization = match minimizer.minimize(&mut u) {
+            Ok(minimization) => minimization,
+            Err(error) => {
+                eprintln!("error in the minimization: {error}");
+                std::process::exit(1);
+            }
+        };
+        let outcome = if minimization.converged {
+            "converged"
+        } else {
+            "did not converge"
+        };
+        eprintln!("Minimization {outcome} in {} steps.", minimization.steps);
+        eprintln!(
+            "    potential energy {:.3} kJ/mol    max force {:.3} kJ/mol/nm",
+            to_kj_per_mol(minimization.energy),
+            to_kj_per_mol(minimization.max_force) * 1e-9,
+        );
+        let mut traj = Trajectory::from_universe(&u, recipe.title.to_owned());
+        traj.add_frame_from_universe(&u);
+        println!("{}", traj.to_gro());
+        return;
+    }
+
+    // Initiate trajectory to save the states in.
+    let mut traj = Trajectory::from_universe(&u, recipe.title.to_owned());
+    traj.add_frame_from_universe(&u);
+
+    // Run this thing!
+    let iters_per_snapshot = recipe.timesteps() / recipe.snapshots();
+    let n_iters = recipe.timesteps();
+    let walltime_start = std::time::Instant::now();
+    while u.time < recipe.end {
+        if let Err(error) = u.step() {
+            eprintln!("\nerror at t = {:.3} ps: {error}", u.time.picoseconds());
+            std::process::exit(1);
+        }
+        if u.iteration.is_multiple_of(iters_per_snapshot) {
+            let remaining_iters = n_iters - u.iteration;
+            let delta_walltime = std::time::Instant::now() - walltime_start;
+            let t_per_iter = delta_walltime.as_secs_f64() / u.iteration as f64;
+            let walltime_remaining = remaining_iters as f64 * t_per_iter;
+            let drift = if nose_hoover {
+                let drift = to_kj_per_mol(u.conserved_energy() - conserved_start);
+                format!(", conserved energy drift {drift:.3} kJ/mol")
+            } else {
+                String::new()
+            };
+            eprint!(
+                "iter {}/{}, t = {:.3} ps{drift}, estimated remaining walltime is {walltime_remaining:.0} s    \r",
+                u.iteration,
+                recipe.timesteps(),
+                u.time.picoseconds()
+            );
+            traj.add_frame_from_universe(&u);
+        }
+    }
+    let walltime_end = std::time::Instant::now();
+    let walltime_runtime = walltime_end - walltime_start;
+
+    // Report some stats about the simulation.
+    eprintln!(
+        "\nSimulated {} particles at {} K for {} ns with a timestep of {} fs in {} s.",
+        u.particles.len(),
+        u.temperature,
+        recipe.time().nanoseconds(),
+        recipe.timestep.femtoseconds(),
+        walltime_runtime.as_secs()
+    );
+    eprintln!(
+        "    {:.3} ps / s    {:.3} ns / day",
+        recipe.time().picoseconds() / walltime_runtime.as_secs_f64(),
+        recipe.time().nanoseconds() / (walltime_runtime.as_secs_f64() / 60.0 / 60.0 / 24.0)
+    );
+    eprintln!(
+        "    potential energy {:.3} kJ/mol    kinetic energy {:.3} kJ/mol",
+        to_kj_per_mol(u.potential_energy()),
+        to_kj_per_mol(u.kinetic_energy()),
+    );
+    if nose_hoover {
+        let conserved = u.conserved_energy();
+        eprintln!(
+            "    conserved energy {:.3} kJ/mol    drift {:.3} kJ/mol/ns",
+            to_kj_per_mol(conserved),
+            to_kj_per_mol(conserved - conserved_start) / recipe.time().nanoseconds(),
+        );
+    }
+    // Report pressure in bar.
+    eprintln!("    pressure {:.3} bar", u.pressure() * 1e-5);
+    let builds = u.neighbour_list.builds();
+    eprintln!(
+        "    rebuilt the neighbour list {builds} times, once every {:.1} steps",
+        u.iteration as f64 / builds as f64
+    );
+    let gro = traj.to_gro();
+    println!("{gro}");
+}
@@ -0,0 +1,717 @@
+use std::collections::VecDeque;
+
+use crate::constraints::ConstraintError;
+use crate::potential::AVOGADRO;
+use crate::universe::Universe;
+use crate::vec3::Vec3;
+
+/// Largest force on any particle below which a minimization has converged (N), 10 kJ/mol/nm.
+pub const DEFAULT_FORCE_TOLERANCE: f64 = 10.0 * 1e3 / AVOGADRO / 1e-9;
+/// Number of steps after which a minimization stops if it has not converged.
+pub const DEFAULT_MINIMIZATION_STEPS: usize = 5000;
+/// Distance the particle with the largest force moves on the first step (meter).
+pub const DEFAULT_MINIMIZATION_STEP: f64 = 0.01e-9;
+/// Number of previous steps from which L-BFGS estimates the curvature of the energy.
+pub const DEFAULT_LBFGS_MEMORY: usize = 10;
+
+/// Number of trial steps after which a line search gives up.
+const MAX_LINE_SEARCH_STEPS: usize = 40;
+
+/// Result of a minimization.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Minimization {
+    /// Potential energy of the minimized structure (J).
+    pub energy: f64,
+    /// Largest force on any particle in the minimized structure (N).
+    pub max_force: f64,
+    /// Number of steps taken.
+    pub steps: usize,
+    /// Whether the largest force fell below the tolerance.
+    pub converged: bool,
+}
+
+/// The potential energy as a function of the positions of some particles.
+pub trait Landscape {
+    /// Returns the positions of the particles.
+    fn positions(&self) -> Vec<Vec3>;
+
+    /// Puts the particles back at positions returned by [`Landscape::positions`].
+    fn set_positions(&mut self, positions: &[Vec3]);
+
+    /// Moves every particle by its displacement. Returns an error if the particles cannot be moved
+    /// there, such as when their constraints cannot be satisfied.
+    fn displace(&mut self, displacements: &[Vec3]) -> Result<(), ConstraintError>;
+
+    /// Returns the energy and the forces on the particles at their positions.
+    fn evaluate(&mut self) -> Result<(f64, Vec<Vec3>), ConstraintError>;
+}
+
+/// The universe, with its particles kept on their constraints. The forces have no components
+/// along the constraints, and the velocities are left as they are.
+impl Landscape for Universe {
+    fn positions(&self) -> Vec<Vec3> {
+        self.particles.iter().map(|p| p.pos).collect()
+    }
+
+    fn set_positions(&mut self, positions: &[Vec3]) {
+        for (particle, &pos) in self.particles.iter_mut().zip(positions) {
+            particle.pos = pos;
+        }
+    }
+
+    fn displace(&mut self, displacements: &[Vec3]) -> Result<(), ConstraintError> {
+        let reference = Landscape::positions(self);
+        let velocities: Vec<Vec3> = self.particles.iter().map(|p| p.vel).collect();
+        for (particle, &displacement) in self.particles.iter_mut().zip(displacements) {
+            particle.pos += displacement;
+        }
+        let constrained = self.constrain_positions(&reference);
+        for (particle, vel) in self.particles.iter_mut().zip(velocities) {
+            particle.vel = vel;
+        }
+        self.apply_boundary_conditions();
+        constrained.map(|_| ())
+    }
+
+    /// Computes the forces on the particles and stores their accelerations.
+    ///
+    /// The constrained forces follow from removing the accelerations along the constraints, as
+    /// RATTLE does for velocities. They are scaled to velocities that move the particle with the
+    /// largest force by a picometer over a time step, which is far below the lengths of the
+    /// constraints and far above the precision of their tolerance.
+    fn evaluate(&mut self) -> Result<(f64, Vec<Vec3>), ConstraintError> {
+        let forces = self.compute_forces();
+        let velocities: Vec<Vec3> = self.particles.iter().map(|p| p.vel).collect();
+        for (particle, force) in self.particles.iter_mut().zip(forces) {
+            particle.acc = force / particle.mass;
+        }
+        let largest = self
+            .particles
+            .iter()
+            .map(|p| p.acc.norm())
+            .fold(0.0, f64::max);
+        let scale = if largest > 0.0 {
+            1e-12 / (self.dt.seconds() * largest)
+        } else {
+            1.0
+        };
+        for particle in &mut self.particles {
+            particle.vel = particle.acc * scale;
+        }
+        let constrained = self.constrain_velocities();
+        let forces = self
+            .particles
+            .iter()
+            .map(|p| p.vel * (p.mass / scale))
+            .collect();
+        for (particle, vel) in self.particles.iter_mut().zip(velocities) {
+            particle.vel = vel;
+        }
+        constrained?;
+        Ok((self.potential_energy(), forces))
+    }
+}
+
+/// Algorithm to minimize the energy of a landscape.
+pub trait Minimizer: std::fmt::Debug {
+    /// Moves the particles of the landscape to a minimum of its energy, until the largest force
+    /// on any particle is below the tolerance or the number of steps has passed. The landscape is
+    /// left evaluated at the minimized positions.
+    fn minimize(&self, landscape: &mut dyn Landscape) -> Result<Minimization, ConstraintError>;
+}
+
+/// Steepest descent with an adaptive step, as in GROMACS.
+///
+/// Every step moves the particles along their forces, such that the particle with the largest
+/// force moves by the step size. If that lowers the potential energy, the step is accepted and
+/// the step size grows by a fifth, and otherwise it is rejected and the step size shrinks to a
+/// fifth.
+///
+/// ```
+/// r' = r + h * F / max(|F|)
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SteepestDescent {
+    /// Largest force on any particle below which the minimization has converged (N).
+    pub tolerance: f64,
+    /// Number of steps after which to stop.
+    pub max_steps: usize,
+    /// Initial step size h (meter).
+    pub step: f64,
+}
+
+impl Default for SteepestDescent {
+    fn default() -> Self {
+        Self {
+            tolerance: DEFAULT_FORCE_TOLERANCE,
+            max_steps: DEFAULT_MINIMIZATION_STEPS,
+            step: DEFAULT_MINIMIZATION_STEP,
+        }
+    }
+}
+
+impl Minimizer for SteepestDescent {
+    fn minimize(&self, landscape: &mut dyn Landscape) -> Result<Minimization, ConstraintError> {
+        let (mut energy, mut forces) = landscape.evaluate()?;
+        let mut max_force = largest(&forces);
+        // Below the precision of the positions, no step can lower the energy any further.
+        let precision = f64::EPSILON * largest(&landscape.positions());
+        let mut step = self.step;
+        let mut steps = 0;
+        while max_force > self.tolerance && steps < self.max_steps && step > precision {
+            steps += 1;
+            let start = landscape.positions();
+            let displacements: Vec<Vec3> = forces.iter().map(|&f| f * (step / max_force)).collect();
+            // A step too large to satisfy the constraints is rejected like one that raises the
+            // energy.
+            let trial = landscape
+                .displace(&displacements)
+                .and_then(|_| landscape.evaluate());
+            match trial {
+                Ok((trial_energy, trial_forces)) if trial_energy < energy => {
+                    (energy, forces) = (trial_energy, trial_forces);
+                    max_force = largest(&forces);
+                    step *= 1.2;
+                }
+                _ => {
+                    landscape.set_positions(&start);
+                    step *= 0.2;
+                }
+            }
+        }
+        finish(landscape, steps, self.tolerance)
+    }
+}
+
+/// Nonlinear conjugate gradient, with the Polak-Ribière formula.
+///
+/// Every step searches along a direction for a point of lower energy, at which the force along the
+/// direction has (nearly) vanished. The next direction is the force there, mixed with the previous
+/// direction such that the directions are conjugate for a quadratic energy.
+///
+/// ```
+/// d' = F' + β * d,   β = max(0, F' ⋅ (F' - F) / (F ⋅ F))
+/// ```
+///
+/// The search starts over along the force when β would be negative.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConjugateGradient {
+    /// Largest force on any particle below which the minimization has converged (N).
+    pub tolerance: f64,
+    /// Number of steps after which to stop.
+    pub max_steps: usize,
+    /// Distance the particle with the largest force moves on the first trial step (meter).
+    pub step: f64,
+}
+
+impl Default for ConjugateGradient {
+    fn default() -> Self {
+        Self {
+            tolerance: DEFAULT_FORCE_TOLERANCE,
+            max_steps: DEFAULT_MINIMIZATION_STEPS,
+            step: DEFAULT_MINIMIZATION_STEP,
+        }
+    }
+}
+
+impl Minimizer for ConjugateGradient {
+    fn minimize(&self, landscape: &mut dyn Landscape) -> Result<Minimization, ConstraintError> {
+        let (mut energy, mut forces) = landscape.evaluate()?;
+        let mut direction = forces.clone();
+        let mut alpha = self.step / largest(&forces);
+        let mut along_force = true;
+        let mut steps = 0;
+        while largest(&forces) > self.tolerance && steps < self.max_steps {
+            steps += 1;
+            let search = line_search(landscape, energy, &forces, &direction, alpha, 0.1)?;
+            let Some(search) = search else {
+                if along_force {
+                    break;
+                }
+                direction = forces.clone();
+                alpha = self.step / largest(&forces);
+                along_force = true;
+                continue;
+            };
+            let beta = (dot(&search.forces, &search.forces) - dot(&search.forces, &forces))
+                / dot(&forces, &forces);
+            let beta = beta.max(0.0);
+            // The next search starts from the step that would change the energy as much as the
+            // last one did.
+            let change = search.alpha * dot(&forces, &direction);
+            direction = (search.forces.iter().zip(&direction))
+                .map(|(&f, &d)| f + d * beta)
+                .collect();
+            alpha = change / dot(&search.forces, &direction);
+            along_force = beta == 0.0;
+            (energy, forces) = (search.energy, search.forces);
+            if dot(&forces, &direction) <= 0.0 {
+                direction = forces.clone();
+                alpha = self.step / largest(&forces);
+                along_force = true;
+            }
+        }
+        finish(landscape, steps, self.tolerance)
+    }
+}
+
+/// Limited-memory BFGS, which moves the particles towards the minimum of a quadratic model of the
+/// energy.
+///
+/// The curvature of the model follows from the changes of the positions and forces over the last
+/// steps, and the minimum of the model is searched for along the direction towards it.
+///
+/// See Nocedal, J. (1980). Math. Comput. 35, 773.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lbfgs {
+    /// Largest force on any particle below which the minimization has converged (N).
+    pub tolerance: f64,
+    /// Number of steps after which to stop.
+    pub max_steps: usize,
+    /// Distance the particle with the largest force moves on the first trial step (meter).
+    pub step: f64,
+    /// Number of previous steps the curvature is estimated from.
+    pub memory: usize,
+}
+
+impl Default for Lbfgs {
+    fn default() -> Self {
+        Self {
+            tolerance: DEFAULT_FORCE_TOLERANCE,
+            max_steps: DEFAULT_MINIMIZATION_STEPS,
+            step: DEFAULT_MINIMIZATION_STEP,
+            memory: DEFAULT_LBFGS_MEMORY,
+        }
+    }
+}
+
+/// The change of the positions and forces over a step of L-BFGS.
+struct Update {
+    /// Change of the positions s.
+    positions: Vec<Vec3>,
+    /// Change of the gradient of the energy y, which is minus the change of the forces.
+    gradient: Vec<Vec3>,
+    /// 1 / (y ⋅ s).
+    rho: f64,
+}
+
+impl Lbfgs {
+    /// Returns the direction towards the minimum of the model, with the inverse of its curvature
+    /// applied to the forces by the two-loop recursion.
+    fn direction(&self, forces: &[Vec3], updates: &VecDeque<Update>) -> Vec<Vec3> {
+        let mut direction = forces.to_vec();
+        let mut alphas = Vec::with_capacity(updates.len());
+        for update in updates.iter().rev() {
+            let alpha = update.rho * dot(&update.positions, &direction);
+            for (d, &y) in direction.iter_mut().zip(&update.gradient) {
+                *d -= y * alpha;
+            }
+            alphas.push(alpha);
+        }
+        // Without any steps yet, the particle with the largest force moves by the step size.
+        let scale = match updates.back() {
+            Some(update) => 1.0 / (update.rho * dot(&update.gradient, &update.gradient)),
+            None => self.step / largest(forces),
+        };
+        for d in &mut direction {
+            *d = *d * scale;
+        }
+        for (update, alpha) in updates.iter().zip(alphas.into_iter().rev()) {
+            let beta = update.rho * dot(&update.gradient, &direction);
+            for (d, &s) in direction.iter_mut().zip(&update.positions) {
+                *d += s * (alpha - beta);
+            }
+        }
+        direction
+    }
+}
+
+impl Minimizer for Lbfgs {
+    fn minimize(&self, landscape: &mut dyn Landscape) -> Result<Minimization, ConstraintError> {
+        let (mut energy, mut forces) = landscape.evaluate()?;
+        let mut updates = VecDeque::with_capacity(self.memory);
+        let mut steps = 0;
+        while largest(&forces) > self.tolerance && steps < self.max_steps {
+            steps += 1;
+            let direction = self.direction(&forces, &updates);
+            let search = line_search(landscape, energy, &forces, &direction, 1.0, 0.9)?;
+            let Some(search) = search else {
+                // Start over from the forces alone, unless that is what failed.
+                if updates.is_empty() {
+                    break;
+                }
+                updates.clear();
+                continue;
+            };
+            let positions: Vec<Vec3> = direction.iter().map(|&d| d * search.alpha).collect();
+            let gradient: Vec<Vec3> = (forces.iter().zip(&search.forces))
+                .map(|(&f, &g)| f - g)
+                .collect();
+            // Only a positive curvature keeps the model convex.
+            let curvature = dot(&positions, &gradient);
+            if curvature > 0.0 {
+                if updates.len() == self.memory {
+                    updates.pop_front();
+                }
+                updates.push_back(Update {
+                    positions,
+                    gradient,
+                    rho: 1.0 / curvature,
+                });
+            }
+            (energy, forces) = (search.energy, search.forces);
+        }
+        finish(landscape, steps, self.tolerance)
+    }
+}
+
+/// A point along a search direction at which the energy is lower, and the force along the
+/// direction has (nearly) vanished.
+struct Search {
+    /// Distance along the direction, in units of the direction.
+    alpha: f64,
+    energy: f64,
+    forces: Vec<Vec3>,
+}
+
+/// Searches along a direction for a point that satisfies the strong Wolfe conditions: the energy
+/// is sufficiently lower than at the start, and the slope along the direction has shrunk to a
+/// fraction `curvature` of that at the start.
+///
+/// ```
+/// E(α) <= E(0) + c * α * E'(0),   |E'(α)| <= curvature * |E'(0)|
+/// ```
+///
+/// The step grows from `initial` until it passes the minimum, after which the minimum is bracketed
+/// by interpolation of the slope. Steps that cannot be made, such as those that break the
+/// constraints, are taken as too long. If the slope does not shrink enough, the lowest point found
+/// will do. The landscape is left at the point found, or at the start if none is found.
+fn line_search(
+    landscape: &mut dyn Landscape,
+    energy: f64,
+    forces: &[Vec3],
+    direction: &[Vec3],
+    initial: f64,
+    curvature: f64,
+) -> Result<Option<Search>, ConstraintError> {
+    let start = landscape.positions();
+    let slope = -dot(forces, direction);
+    let (mut lo, mut lo_energy, mut lo_slope) = (0.0, energy, slope);
+    let mut hi: Option<(f64, f64)> = None;
+    let mut alpha = initial;
+    for _ in 0..MAX_LINE_SEARCH_STEPS {
+        let displacements: Vec<Vec3> = direction.iter().map(|&d| d * alpha).collect();
+        let trial = landscape
+            .displace(&displacements)
+            .and_then(|_| landscape.evaluate());
+        match trial {
+            Ok((trial_energy, trial_forces)) => {
+                let trial_slope = -dot(&trial_forces, direction);
+                let sufficient = trial_energy <= energy + 1e-4 * alpha * slope;
+                if !trial_energy.is_finite() || !sufficient || trial_energy >= lo_energy {
+                    hi = Some((alpha, trial_slope));
+                } else if trial_slope.abs() <= -curvature * slope {
+                    return Ok(Some(Search {
+                        alpha,
+                        energy: trial_energy,
+                        forces: trial_forces,
+                    }));
+                } else if trial_slope > 0.0 {
+                    hi = Some((alpha, trial_slope));
+                } else {
+                    (lo, lo_energy, lo_slope) = (alpha, trial_energy, trial_slope);
+                }
+            }
+            Err(_) => hi = Some((alpha, f64::NAN)),
+        }
+        landscape.set_positions(&start);
+        alpha = match hi {
+            None => 4.0 * alpha,
+            Some((hi, hi_slope)) => {
+                // Where the slope vanishes if it changes sign, and halfway otherwise, away from
+                // the ends of the bracket.
+                let width = hi - lo;
+                let guess = if hi_slope > 0.0 {
+                    lo - lo_slope * width / (hi_slope - lo_slope)
+                } else {
+                    lo + 0.5 * width
+                };
+                guess.clamp(lo + 0.1 * width, hi - 0.1 * width)
+            }
+        };
+    }
+    // Settle for the lowest energy found, if lower than at the start.
+    if lo > 0.0 {
+        let displacements: Vec<Vec3> = direction.iter().map(|&d| d * lo).collect();
+        landscape.displace(&displacements)?;
+        let (energy, forces) = landscape.evaluate()?;
+        return Ok(Some(Search {
+            alpha: lo,
+            energy,
+            forces,
+        }));
+    }
+    Ok(None)
+}
+
+/// Evaluates the landscape at the minimized positions, since the last evaluation may have been of
+/// a rejected step.
+fn finish(
+    landscape: &mut dyn Landscape,
+    steps: usize,
+    tolerance: f64,
+) -> Result<Minimization, ConstraintError> {
+    let (energy, forces) = landscape.evaluate()?;
+    let max_force = largest(&forces);
+    Ok(Minimization {
+        energy,
+        max_force,
+        steps,
+        converged: max_force <= tolerance,
+    })
+}
+
+/// Returns the sum of the dot products of two sets of vectors.
+fn dot(a: &[Vec3], b: &[Vec3]) -> f64 {
+    a.iter().zip(b).map(|(a, b)| a.dot(*b)).sum()
+}
+
+/// Returns the largest norm of some vectors.
+fn largest(vectors: &[Vec3]) -> f64 {
+    vectors.iter().map(|v| v.norm()).fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::constraints::Constraint;
+    use crate::potential::Modifier;
+    use crate::topology::{Molecule, Topology};
+    use crate::universe::tests::argon_universe;
+    use crate::universe::{minimum_image, Particle};
+
+    /// Returns an argon-like gas of particles at random, overlapping positions, with every other
+    /// pair constrained if asked for.
+    fn overlapping(n: usize, constrained: bool) -> Universe {
+        let mut rng = StdRng::seed_from_u64(37);
+        let length = 2e-9;
+        let mut particles = Vec::new();
+        let mut topology = Topology::new();
+        let dimer = Molecule {
+            name: "AR2".to_string(),
+            species: vec![0, 0],
+            bonds: Vec::new(),
+            angles: Vec::new(),
+            dihedrals: Vec::new(),
+            impropers: Vec::new(),
+            constraints: vec![Constraint {
+                particles: (0, 1),
+                length: 0.38e-9,
+            }],
+            settles: Vec::new(),
+        };
+        for index in 0..n {
+            let pos = Vec3::new(rng.gen(), rng.gen(), rng.gen()) * length;
+            let pos = pos - Vec3::one() * (0.5 * length);
+            particles.push(Particle::new(pos, Vec3::zero(), Vec3::zero(), 6.6e-26));
+            if constrained && index % 2 == 1 {
+                topology.add_molecule(&dimer, index - 1);
+            }
+        }
+        let mut u =
+            argon_universe(&particles, length, 0.9e-9, Modifier::PotentialShift).topology(topology);
+        u.prepare().unwrap();
+        u
+    }
+
+    #[test]
+    fn steepest_descent_finds_the_minimum_of_a_pair() {
+        let sigma = 0.34e-9;
+        let epsilon = 0.996e3 / AVOGADRO;
+        let particles = [
+            Particle::new(Vec3::zero(), Vec3::zero(), Vec3::zero(), 6.6e-26),
+            Particle::new(
+                Vec3::new(0.3e-9, 0.0, 0.0),
+                Vec3::zero(),
+                Vec3::zero(),
+                6.6e-26,
+            ),
+        ];
+        let mut u = argon_universe(&particles, 5e-9, 2e-9, Modifier::None);
+        u.prepare().unwrap();
+        let steepest_descent = SteepestDescent {
+            tolerance: 1e-4 * DEFAULT_FORCE_TOLERANCE,
+            ..Default::default()
+        };
+        let minimization = steepest_descent.minimize(&mut u).unwrap();
+
+        // The pair is at its lowest energy, -ε, at a distance of 2^(1/6) σ.
+        assert!(minimization.converged);
+        let distance = (u.particles[1].pos - u.particles[0].pos).norm();
+        assert!((distance - 2f64.powf(1.0 / 6.0) * sigma).abs() < 1e-4 * sigma);
+        assert!((minimization.energy + epsilon).abs() < 1e-6 * epsilon);
+    }
+
+    #[test]
+    fn steepest_descent_relaxes_overlapping_particles() {
+        for constrained in [false, true] {
+            let mut u = overlapping(64, constrained);
+            let start = u.potential_energy();
+            let minimization = SteepestDescent::default().minimize(&mut u).unwrap();
+            assert!(minimization.converged);
+            assert!(minimization.energy < 0.0 && start > 0.0);
+            assert_eq!(minimization.energy, u.potential_energy());
+            for constraint in &u.topology.constraints {
+                let (i, j) = constraint.particles;
+                let r = minimum_image(u.particles[i].pos - u.particles[j].pos, u.boundary);
+                assert!((r.norm() - constraint.length).abs() < 1e-6 * constraint.length);
+            }
+            // The minimized structure can be run.
+            u.steps(100).unwrap();
+        }
+    }
+
+    /// A landscape of a closed-form energy, in arbitrary units.
+    struct Analytic {
+        positions: Vec<Vec3>,
+        energy: fn(&[Vec3]) -> (f64, Vec<Vec3>),
+    }
+
+    impl Landscape for Analytic {
+        fn positions(&self) -> Vec<Vec3> {
+            self.positions.clone()
+        }
+
+        fn set_positions(&mut self, positions: &[Vec3]) {
+            self.positions = positions.to_vec();
+        }
+
+        fn displace(&mut self, displacements: &[Vec3]) -> Result<(), ConstraintError> {
+            for (pos, &displacement) in self.positions.iter_mut().zip(displacements) {
+                *pos += displacement;
+            }
+            Ok(())
+        }
+
+        fn evaluate(&mut self) -> Result<(f64, Vec<Vec3>), ConstraintError> {
+            Ok((self.energy)(&self.positions))
+        }
+    }
+
+    /// A narrow quadratic bowl around (1, 2, 3) for every particle, with force constants from 1
+    /// to 100.
+    fn bowl(positions: &[Vec3]) -> (f64, Vec<Vec3>) {
+        let mut energy = 0.0;
+        let mut forces = Vec::new();
+        for (index, &pos) in positions.iter().enumerate() {
+            let k = Vec3::new(1.0, 10.0, 100.0) * (index + 1) as f64 / positions.len() as f64;
+            let r = pos - Vec3::new(1.0, 2.0, 3.0);
+            energy += 0.5 * (k * r).dot(r);
+            forces.push(k * r * -1.0);
+        }
+        (energy, forces)
+    }
+
+    /// The banana-shaped valley of Rosenbrock in x and y, and a parabola in z, with its minimum
+    /// at (1, 1, 0).
+    fn rosenbrock(positions: &[Vec3]) -> (f64, Vec<Vec3>) {
+        let Vec3 { x, y, z } = positions[0];
+        let energy = (1.0 - x).powi(2) + 100.0 * (y - x * x).powi(2) + z * z;
+        let gradient = Vec3::new(
+            -2.0 * (1.0 - x) - 400.0 * x * (y - x * x),
+            200.0 * (y - x * x),
+            2.0 * z,
+        );
+        (energy, vec![gradient * -1.0])
+    }
+
+    #[test]
+    fn minimizers_converge_on_analytic_landscapes() {
+        let (tolerance, max_steps, step) = (1e-8, 1000, 0.1);
+        let conjugate_gradient = ConjugateGradient {
+            tolerance,
+            max_steps,
+            step,
+        };
+        let lbfgs = Lbfgs {
+            tolerance,
+            max_steps,
+            step,
+            memory: DEFAULT_LBFGS_MEMORY,
+        };
+        let minimizers: [&dyn Minimizer; 2] = [&conjugate_gradient, &lbfgs];
+        for minimizer in minimizers {
+            let mut landscape = Analytic {
+                positions: vec![Vec3::zero(), Vec3::one(), Vec3::one() * -2.0],
+                energy: bowl,
+            };
+            let minimization = minimizer.minimize(&mut landscape).unwrap();
+            assert!(minimization.converged, "{minimizer:?}");
+            for pos in landscape.positions {
+                assert!((pos - Vec3::new(1.0, 2.0, 3.0)).norm() < 1e-6);
+            }
+
+            let mut landscape = Analytic {
+                positions: vec![Vec3::new(-1.2, 1.0, 0.5)],
+                energy: rosenbrock,
+            };
+            let minimization = minimizer.minimize(&mut landscape).unwrap();
+            assert!(minimization.converged, "{minimizer:?}");
+            assert!((landscape.positions[0] - Vec3::new(1.0, 1.0, 0.0)).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn minimizers_find_the_global_minimum_of_a_cluster() {
+        // Thirteen Lennard-Jones particles form an icosahedron around a central particle, with
+        // an energy of -44.326801 ε. Start from a distorted one, with the vertices at (0, ±1, ±φ)
+        // and their cyclic permutations.
+        let (sigma, epsilon) = (0.34e-9, 0.996e3 / AVOGADRO);
+        let phi = 0.5 * (1.0 + 5f64.sqrt());
+        let mut vertices = vec![Vec3::zero()];
+        for (a, b) in [(1.0, phi), (1.0, -phi), (-1.0, phi), (-1.0, -phi)] {
+            vertices.push(Vec3::new(0.0, a, b));
+            vertices.push(Vec3::new(a, b, 0.0));
+            vertices.push(Vec3::new(b, 0.0, a));
+        }
+        let mut rng = StdRng::seed_from_u64(41);
+        let radius = 2f64.powf(1.0 / 6.0) * sigma;
+        let particles: Vec<Particle> = vertices
+            .into_iter()
+            .map(|vertex| {
+                let jitter = Vec3::new(rng.gen(), rng.gen(), rng.gen()) - Vec3::one() * 0.5;
+                let pos = vertex * (radius / (1.0 + phi * phi).sqrt()) + jitter * (0.2 * sigma);
+                Particle::new(pos, Vec3::zero(), Vec3::zero(), 6.6e-26)
+            })
+            .collect();
+
+        let tolerance = 1e-3 * DEFAULT_FORCE_TOLERANCE;
+        let steepest_descent = SteepestDescent {
+            tolerance,
+            ..Default::default()
+        };
+        let conjugate_gradient = ConjugateGradient {
+            tolerance,
+            ..Default::default()
+        };
+        let lbfgs = Lbfgs {
+            tolerance,
+            ..Default::default()
+        };
+        let minimize = |minimizer: &dyn Minimizer| {
+            let mut u = argon_universe(&particles, 5e-9, 2e-9, Modifier::None);
+            u.prepare().unwrap();
+            let minimization = minimizer.minimize(&mut u).unwrap();
+            assert!(minimization.converged, "{minimizer:?}");
+            let energy = minimization.energy / epsilon;
+            assert!((energy + 44.326801).abs() < 1e-6 * 44.326801, "{energy}");
+            minimization.steps
+        };
+        // Both take fewer steps than steepest descent.
+        let steps = minimize(&steepest_descent);
+        assert!(minimize(&conjugate_gradient) < steps);
+        assert!(minimize(&lbfgs) < steps);
+    }
+}
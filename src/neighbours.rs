@@ -0,0 +1,381 @@
+use crate::universe::{minimum_image, Particle};
+use crate::vec3::Vec3;
+
+/// Offsets of a cell and the 26 cells that surround it.
+#[rustfmt::skip]
+const NEIGHBOURS: [(isize, isize, isize); 9 * 3] = [
+    (-1, -1, -1), (-1, -1,  0), (-1, -1,  1),
+    (-1,  0, -1), (-1,  0,  0), (-1,  0,  1),
+    (-1,  1, -1), (-1,  1,  0), (-1,  1,  1),
+    ( 0, -1, -1), ( 0, -1,  0), ( 0, -1,  1),
+    ( 0,  0, -1), ( 0,  0,  0), ( 0,  0,  1),
+    ( 0,  1, -1), ( 0,  1,  0), ( 0,  1,  1),
+    ( 1, -1, -1), ( 1, -1,  0), ( 1, -1,  1),
+    ( 1,  0, -1), ( 1,  0,  0), ( 1,  0,  1),
+    ( 1,  1, -1), ( 1,  1,  0), ( 1,  1,  1),
+];
+
+/// A linked-cell list.
+///
+/// The periodic box is divided into cells that are at least as wide as the interaction cutoff.
+/// Any particle within the cutoff of some particle must then be in the same cell or in one of
+/// the 26 cells surrounding it. This makes finding the neighbours of all particles O(N) rather
+/// than O(N^2).
+///
+/// The particles in each cell are stored as a linked list: `head` holds the first particle of
+/// each cell, and `next` holds the particle that follows each particle in its cell.
+#[derive(Debug, Clone)]
+pub struct CellList {
+    /// Number of cells along each axis.
+    dims: [usize; 3],
+    /// Size of a cell along each axis (meter).
+    cell_size: Vec3,
+    boundary: Vec3,
+    head: Vec<Option<usize>>,
+    next: Vec<Option<usize>>,
+}
+
+impl CellList {
+    /// Creates a new, empty [`CellList`] for some boundary and interaction cutoff (meter).
+    pub fn new(boundary: Vec3, cutoff: f64) -> Self {
+        let n_cells = |length: f64| ((length / cutoff).floor() as usize).max(1);
+        let dims = [
+            n_cells(boundary.x),
+            n_cells(boundary.y),
+            n_cells(boundary.z),
+        ];
+        let cell_size = boundary / Vec3::new(dims[0] as f64, dims[1] as f64, dims[2] as f64);
+        Self {
+            dims,
+            cell_size,
+            boundary,
+            head: vec![None; dims[0] * dims[1] * dims[2]],
+            next: Vec::new(),
+        }
+    }
+
+    /// Sort the particles into their cells.
+    pub fn build(mut self, particles: &[Particle]) -> Self {
+        self.head.fill(None);
+        self.next.clear();
+        self.next.resize(particles.len(), None);
+        for (index, particle) in particles.iter().enumerate() {
+            let cell = self.flat_index(self.cell_of(particle.pos));
+            // Prepend the particle to the list of its cell.
+            self.next[index] = self.head[cell];
+            self.head[cell] = Some(index);
+        }
+        self
+    }
+
+    /// Returns the cell coordinates of some position.
+    ///
+    /// Positions that lie (slightly) outside of the boundary are put in the nearest cell.
+    fn cell_of(&self, pos: Vec3) -> [usize; 3] {
+        let Vec3 { x, y, z } = (pos + self.boundary * 0.5) / self.cell_size;
+        let clamp = |v: f64, n: usize| (v.floor().max(0.0) as usize).min(n - 1);
+        [
+            clamp(x, self.dims[0]),
+            clamp(y, self.dims[1]),
+            clamp(z, self.dims[2]),
+        ]
+    }
+
+    fn flat_index(&self, [x, y, z]: [usize; 3]) -> usize {
+        (x * self.dims[1] + y) * self.dims[2] + z
+    }
+
+    /// Returns the cell that lies at some offset from a cell, wrapping around the periodic
+    /// boundary.
+    fn neighbour_cell(&self, cell: [usize; 3], offset: (isize, isize, isize)) -> usize {
+        let wrap = |c: usize, d: isize, n: usize| (c as isize + d).rem_euclid(n as isize) as usize;
+        let (dx, dy, dz) = offset;
+        self.flat_index([
+            wrap(cell[0], dx, self.dims[0]),
+            wrap(cell[1], dy, self.dims[1]),
+            wrap(cell[2], dz, self.dims[2]),
+        ])
+    }
+
+    /// Returns the indices of the particles in some cell.
+    fn particles_in(&self, cell: usize) -> impl Iterator<Item = usize> + '_ {
+        std::iter::successors(self.head[cell], |&index| self.next[index])
+    }
+
+    /// Returns the indices of all particles that may lie within the cutoff of some position.
+    ///
+    /// When there are fewer than three cells along some axis, the same cell borders the cell of
+    /// the position on more than one side. Each cell is only visited once, such that every
+    /// particle is returned at most once.
+    pub fn neighbours(&self, pos: Vec3) -> impl Iterator<Item = usize> + '_ {
+        let cell = self.cell_of(pos);
+        let mut cells: Vec<usize> = NEIGHBOURS
+            .iter()
+            .map(|&offset| self.neighbour_cell(cell, offset))
+            .collect();
+        cells.sort_unstable();
+        cells.dedup();
+        cells
+            .into_iter()
+            .flat_map(move |neighbour| self.particles_in(neighbour))
+    }
+}
+
+/// A Verlet neighbour list.
+///
+/// For every particle, the list stores the indices of the particles with a higher index than its
+/// own that lie within the cutoff plus some buffer distance, the skin. Since F_ij = -F_ji, every
+/// pair only needs to be visited once. As long as no particle has moved more than half the skin
+/// since the list was built, every pair within the cutoff is guaranteed to be in the list, and the
+/// list can be reused for the next step.
+///
+/// No periodic images are stored. Distances are measured according to the minimum-image
+/// convention, both when the list is built and when its pairs are used.
+#[derive(Debug, Clone)]
+pub struct VerletList {
+    /// Buffer distance added to the cutoff (meter).
+    skin: f64,
+    /// Maximum number of steps between rebuilds, if any.
+    interval: Option<usize>,
+    /// For every particle, the indices of its neighbours with a higher index.
+    neighbours: Vec<Vec<usize>>,
+    /// Positions of the particles at the time the list was last built.
+    reference: Vec<Vec3>,
+    /// Number of steps since the list was last built.
+    age: usize,
+    /// Number of times the list has been built.
+    builds: usize,
+}
+
+impl VerletList {
+    /// Creates a new, empty [`VerletList`] with some skin (meter).
+    pub fn new(skin: f64) -> Self {
+        Self {
+            skin,
+            interval: None,
+            neighbours: Vec::new(),
+            reference: Vec::new(),
+            age: 0,
+            builds: 0,
+        }
+    }
+
+    /// Set the skin.
+    pub fn skin(mut self, skin: f64) -> Self {
+        self.skin = skin;
+        self
+    }
+
+    /// Set the maximum number of steps between rebuilds.
+    pub fn interval(mut self, interval: Option<usize>) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the number of times the list has been built.
+    pub fn builds(&self) -> usize {
+        self.builds
+    }
+
+    /// Returns whether the list must be rebuilt before it can be used for the current positions.
+    pub fn is_stale(&self, boundary: Vec3, particles: &[Particle]) -> bool {
+        if self.reference.len() != particles.len() {
+            return true;
+        }
+        if self.interval.is_some_and(|interval| self.age >= interval) {
+            return true;
+        }
+        let max_displacement = 0.5 * self.skin;
+        self.reference
+            .iter()
+            .zip(particles)
+            .any(|(&reference, particle)| {
+                minimum_image(particle.pos - reference, boundary).norm() > max_displacement
+            })
+    }
+
+    /// Build the list from scratch for some boundary and interaction cutoff (meter).
+    ///
+    /// Pairs for which `excluded` returns `true` are left out of the list.
+    pub fn build(
+        &mut self,
+        boundary: Vec3,
+        cutoff: f64,
+        particles: &[Particle],
+        excluded: impl Fn(usize, usize) -> bool,
+    ) {
+        let radius = cutoff + self.skin;
+        let cells = CellList::new(boundary, radius).build(particles);
+        self.neighbours.clear();
+        self.neighbours
+            .extend(particles.iter().enumerate().map(|(index, particle)| {
+                cells
+                    .neighbours(particle.pos)
+                    .filter(|&other_index| {
+                        // The pairs with a lower index are stored for the other particle.
+                        if index >= other_index || excluded(index, other_index) {
+                            return false;
+                        }
+                        let r = minimum_image(particle.pos - particles[other_index].pos, boundary);
+                        r.norm() < radius
+                    })
+                    .collect()
+            }));
+        self.reference.clear();
+        self.reference.extend(particles.iter().map(|p| p.pos));
+        self.age = 0;
+        self.builds += 1;
+    }
+
+    /// Mark that a step has been taken with the current list.
+    pub fn tick(&mut self) {
+        self.age += 1;
+    }
+
+    /// Returns the neighbours of some particle that have a higher index.
+    pub fn neighbours(&self, index: usize) -> &[usize] {
+        &self.neighbours[index]
+    }
+
+    /// Returns every pair of neighbours once.
+    pub fn pairs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.neighbours
+            .iter()
+            .enumerate()
+            .flat_map(|(index, neighbours)| {
+                neighbours
+                    .iter()
+                    .map(move |&other_index| (index, other_index))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    /// Returns `n` particles at random positions in the boundary.
+    fn random_particles(n: usize, boundary: Vec3) -> Vec<Particle> {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut random = |length: f64| rng.gen_range(-0.5 * length..0.5 * length);
+        (0..n)
+            .map(|_| {
+                let pos = Vec3::new(random(boundary.x), random(boundary.y), random(boundary.z));
+                Particle::new(pos, Vec3::zero(), Vec3::zero(), 1e-26)
+            })
+            .collect()
+    }
+
+    /// Returns the pairs within the cutoff, with the lower index first, by checking all of them.
+    fn brute_force_pairs(
+        particles: &[Particle],
+        boundary: Vec3,
+        cutoff: f64,
+    ) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for i in 0..particles.len() {
+            for j in i + 1..particles.len() {
+                let r = minimum_image(particles[i].pos - particles[j].pos, boundary);
+                if r.norm() < cutoff {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn cell_list_finds_the_pairs_of_a_brute_force_search() {
+        // Two cells along x and y, such that the same cell lies on both sides, and four along z.
+        // None of the cells is a whole multiple of the cutoff wide.
+        let boundary = Vec3::new(1.9e-9, 2.5e-9, 4.1e-9);
+        let cutoff = 0.9e-9;
+        let particles = random_particles(400, boundary);
+        let cells = CellList::new(boundary, cutoff).build(&particles);
+        assert_eq!(cells.dims, [2, 2, 4]);
+
+        let mut pairs = Vec::new();
+        for (index, particle) in particles.iter().enumerate() {
+            let candidates: Vec<usize> = cells.neighbours(particle.pos).collect();
+            let mut unique = candidates.clone();
+            unique.sort_unstable();
+            unique.dedup();
+            assert_eq!(unique.len(), candidates.len(), "particle {index}");
+            pairs.extend(
+                candidates
+                    .into_iter()
+                    .filter(|&other_index| {
+                        let r = minimum_image(particle.pos - particles[other_index].pos, boundary);
+                        index < other_index && r.norm() < cutoff
+                    })
+                    .map(|other_index| (index, other_index)),
+            );
+        }
+        pairs.sort_unstable();
+        assert_eq!(pairs, brute_force_pairs(&particles, boundary, cutoff));
+    }
+
+    #[test]
+    fn verlet_list_is_rebuilt_after_half_the_skin() {
+        let boundary = Vec3::new(1.9e-9, 2.5e-9, 4.1e-9);
+        let (cutoff, skin) = (0.8e-9, 0.1e-9);
+        let mut particles = random_particles(100, boundary);
+        let mut list = VerletList::new(skin);
+        assert!(list.is_stale(boundary, &particles));
+        list.build(boundary, cutoff, &particles, |_, _| false);
+        assert!(!list.is_stale(boundary, &particles));
+
+        let start = particles[7].pos;
+        particles[7].pos = start + Vec3::new(0.0, 0.0, 0.45 * skin);
+        assert!(!list.is_stale(boundary, &particles));
+        particles[7].pos = start + Vec3::new(0.0, 0.0, 0.55 * skin);
+        assert!(list.is_stale(boundary, &particles));
+        assert_eq!(list.builds(), 1);
+    }
+
+    #[test]
+    fn verlet_list_is_rebuilt_at_the_interval() {
+        let boundary = Vec3::new(1.9e-9, 2.5e-9, 4.1e-9);
+        let particles = random_particles(100, boundary);
+        let mut list = VerletList::new(0.1e-9).interval(Some(5));
+        list.build(boundary, 0.8e-9, &particles, |_, _| false);
+        for _ in 0..4 {
+            list.tick();
+            assert!(!list.is_stale(boundary, &particles));
+        }
+        list.tick();
+        assert!(list.is_stale(boundary, &particles));
+    }
+
+    #[test]
+    fn verlet_list_holds_every_pair_until_it_is_stale() {
+        let boundary = Vec3::new(1.9e-9, 2.5e-9, 4.1e-9);
+        let (cutoff, skin) = (0.8e-9, 0.1e-9);
+        let mut particles = random_particles(400, boundary);
+        let mut list = VerletList::new(skin);
+        list.build(boundary, cutoff, &particles, |_, _| false);
+
+        // Move every particle by just under half the skin.
+        let mut rng = StdRng::seed_from_u64(3);
+        for particle in &mut particles {
+            let direction = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            );
+            particle.pos += direction * (0.49 * skin / direction.norm());
+        }
+        assert!(!list.is_stale(boundary, &particles));
+        let mut pairs: Vec<(usize, usize)> = list
+            .pairs()
+            .filter(|&(i, j)| {
+                assert!(i < j);
+                minimum_image(particles[i].pos - particles[j].pos, boundary).norm() < cutoff
+            })
+            .collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs, brute_force_pairs(&particles, boundary, cutoff));
+    }
+}
@@ -0,0 +1,355 @@
+use std::f64::consts::PI;
+
+/// Avogadro constant (1 / mol).
+pub const AVOGADRO: f64 = 6.02214076e23;
+
+/// An interaction between a pair of particles that only depends on their distance.
+pub trait Potential: std::fmt::Debug {
+    /// Returns the potential energy (J) of a pair of particles at a distance `r` (meter).
+    fn energy(&self, r: f64) -> f64;
+
+    /// Returns the magnitude of the force (N) between a pair of particles at a distance `r`
+    /// (meter).
+    ///
+    /// ```
+    /// F(r) = - dV/dr
+    /// ```
+    ///
+    /// A positive force pushes the particles apart.
+    fn force(&self, r: f64) -> f64;
+
+    /// Returns the integral of the energy over all distances beyond the cutoff (J⋅m^3).
+    ///
+    /// ```
+    /// ∫_{r_c}^∞ V(r) * 4 * π * r^2 dr
+    /// ```
+    ///
+    /// This is used to correct the energy for the interactions that are neglected by truncating
+    /// the potential. Potentials without a known tail are not corrected.
+    fn tail_energy(&self, _cutoff: f64) -> f64 {
+        0.0
+    }
+
+    /// Returns the integral of the virial over all distances beyond the cutoff (J⋅m^3).
+    ///
+    /// ```
+    /// ∫_{r_c}^∞ r * F(r) * 4 * π * r^2 dr
+    /// ```
+    ///
+    /// This is used to correct the pressure for the interactions that are neglected by truncating
+    /// the potential. Potentials without a known tail are not corrected.
+    fn tail_virial(&self, _cutoff: f64) -> f64 {
+        0.0
+    }
+
+    /// Returns the plain potential that this potential modifies at the cutoff, if it does.
+    fn unmodified(&self) -> Option<&dyn Potential> {
+        None
+    }
+}
+
+/// Interatomic potential according to
+/// [Lennard-Jones potential](https://en.wikipedia.org/wiki/Lennard-Jones_potential).
+///
+/// ```
+/// V_LJ(r) = 4 * ε * [ ( σ / r ) ^ 12 − ( σ / r ) ^ 6 ]
+/// F_LJ(r) = 24 * ε / r * [ 2 * ( σ / r ) ^ 12 − ( σ / r ) ^ 6 ]
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LennardJones {
+    /// Depth of the potential well (J).
+    pub epsilon: f64,
+    /// Distance at which the potential crosses zero (meter).
+    pub sigma: f64,
+}
+
+impl LennardJones {
+    /// Creates a new [`LennardJones`] potential from a well depth (J) and a distance at which the
+    /// potential crosses zero (meter).
+    pub const fn new(epsilon: f64, sigma: f64) -> Self {
+        Self { epsilon, sigma }
+    }
+
+    /// Creates a new [`LennardJones`] potential from a molar well depth (J/mol) and a distance at
+    /// which the potential crosses zero (meter).
+    pub fn from_molar(epsilon: f64, sigma: f64) -> Self {
+        Self::new(epsilon / AVOGADRO, sigma)
+    }
+}
+
+impl Potential for LennardJones {
+    #[inline]
+    fn energy(&self, r: f64) -> f64 {
+        let frac_pow_6 = (self.sigma / r).powi(6);
+        4.0 * self.epsilon * (frac_pow_6 * frac_pow_6 - frac_pow_6)
+    }
+
+    #[inline]
+    fn force(&self, r: f64) -> f64 {
+        let frac_pow_6 = (self.sigma / r).powi(6);
+        24.0 * self.epsilon / r * (2.0 * frac_pow_6 * frac_pow_6 - frac_pow_6)
+    }
+
+    /// ```
+    /// 16 * π * ε * σ^3 * [ 1/9 * ( σ / r_c ) ^ 9 − 1/3 * ( σ / r_c ) ^ 3 ]
+    /// ```
+    fn tail_energy(&self, cutoff: f64) -> f64 {
+        let frac_pow_3 = (self.sigma / cutoff).powi(3);
+        let frac_pow_9 = frac_pow_3.powi(3);
+        16.0 * PI * self.epsilon * self.sigma.powi(3) * (frac_pow_9 / 9.0 - frac_pow_3 / 3.0)
+    }
+
+    /// ```
+    /// 96 * π * ε * σ^3 * [ 2/9 * ( σ / r_c ) ^ 9 − 1/3 * ( σ / r_c ) ^ 3 ]
+    /// ```
+    fn tail_virial(&self, cutoff: f64) -> f64 {
+        let frac_pow_3 = (self.sigma / cutoff).powi(3);
+        let frac_pow_9 = frac_pow_3.powi(3);
+        96.0 * PI * self.epsilon * self.sigma.powi(3) * (2.0 * frac_pow_9 / 9.0 - frac_pow_3 / 3.0)
+    }
+}
+
+/// The interactions between every pair of species.
+///
+/// Since the interaction between species `a` and `b` is the same as between `b` and `a`, only one
+/// potential is stored for each unordered pair.
+#[derive(Debug)]
+pub struct PairTable {
+    /// Number of species.
+    n: usize,
+    potentials: Vec<Box<dyn Potential>>,
+}
+
+impl PairTable {
+    /// Creates a new [`PairTable`] for `n` species, from a function that returns the potential
+    /// between two species.
+    pub fn new(n: usize, mut potential: impl FnMut(usize, usize) -> Box<dyn Potential>) -> Self {
+        let mut potentials = Vec::with_capacity(n * (n + 1) / 2);
+        for a in 0..n {
+            for b in a..n {
+                potentials.push(potential(a, b));
+            }
+        }
+        Self { n, potentials }
+    }
+
+    /// Creates a new [`PairTable`] for a single species.
+    pub fn single(potential: impl Potential + 'static) -> Self {
+        Self {
+            n: 1,
+            potentials: vec![Box::new(potential)],
+        }
+    }
+
+    /// Returns the number of species.
+    pub fn species(&self) -> usize {
+        self.n
+    }
+
+    /// Truncate every potential at some cutoff (meter) with some modifier.
+    pub fn truncated(self, cutoff: f64, modifier: Modifier) -> Self {
+        let potentials = self
+            .potentials
+            .into_iter()
+            .map(|potential| {
+                Box::new(Truncated::new(potential, cutoff, modifier)) as Box<dyn Potential>
+            })
+            .collect();
+        Self {
+            n: self.n,
+            potentials,
+        }
+    }
+
+    /// Returns the potential between two species.
+    #[inline]
+    pub fn get(&self, a: usize, b: usize) -> &dyn Potential {
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        // Skip the rows of the species before a, which have n, n - 1, ..., n - a + 1 entries.
+        let row = a * self.n - a * (a.saturating_sub(1)) / 2;
+        self.potentials[row + b - a].as_ref()
+    }
+
+    /// Returns the potential between two species without its modification at the cutoff, for the
+    /// 1-4 pairs, which interact at any distance.
+    #[inline]
+    pub fn get_unmodified(&self, a: usize, b: usize) -> &dyn Potential {
+        let potential = self.get(a, b);
+        potential.unmodified().unwrap_or(potential)
+    }
+}
+
+/// Modification of a potential that is truncated at the cutoff.
+///
+/// A plain truncation makes the energy jump whenever a pair crosses the cutoff, which shows up as
+/// a drift of the total energy. The modifiers remove this discontinuity, and that of the force.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Modifier {
+    /// Plain truncation.
+    #[default]
+    None,
+    /// Shift the potential such that it is zero at the cutoff.
+    ///
+    /// ```
+    /// V_s(r) = V(r) - V(r_c)
+    /// ```
+    PotentialShift,
+    /// Shift the force such that it is zero at the cutoff, and adjust the potential to match.
+    ///
+    /// ```
+    /// F_s(r) = F(r) - F(r_c)
+    /// V_s(r) = V(r) - V(r_c) + (r - r_c) * F(r_c)
+    /// ```
+    ForceShift,
+    /// Smoothly switch off the potential between `r_switch` (meter) and the cutoff (CHARMM).
+    ///
+    /// ```
+    /// S(r) = (r_c^2 - r^2)^2 * (r_c^2 + 2 * r^2 - 3 * r_s^2) / (r_c^2 - r_s^2)^3
+    /// V_s(r) = V(r) * S(r)
+    /// ```
+    Switch { r_switch: f64 },
+}
+
+/// A [`Potential`] that is truncated at a cutoff, with some [`Modifier`].
+#[derive(Debug)]
+pub struct Truncated {
+    potential: Box<dyn Potential>,
+    /// Cutoff in meters.
+    cutoff: f64,
+    modifier: Modifier,
+    /// Energy at the cutoff (J).
+    energy_at_cutoff: f64,
+    /// Force at the cutoff (N).
+    force_at_cutoff: f64,
+}
+
+impl Truncated {
+    /// Creates a new [`Truncated`] potential with some cutoff (meter) and modifier.
+    pub fn new(potential: Box<dyn Potential>, cutoff: f64, modifier: Modifier) -> Self {
+        Self {
+            energy_at_cutoff: potential.energy(cutoff),
+            force_at_cutoff: potential.force(cutoff),
+            potential,
+            cutoff,
+            modifier,
+        }
+    }
+
+    /// Returns the switching function and its derivative to r at some distance.
+    fn switch(&self, r: f64, r_switch: f64) -> (f64, f64) {
+        if r <= r_switch {
+            return (1.0, 0.0);
+        }
+        let rc2 = self.cutoff * self.cutoff;
+        let rs2 = r_switch * r_switch;
+        let r2 = r * r;
+        let denominator = (rc2 - rs2).powi(3);
+        let s = (rc2 - r2).powi(2) * (rc2 + 2.0 * r2 - 3.0 * rs2) / denominator;
+        let ds = 12.0 * r * (rc2 - r2) * (rs2 - r2) / denominator;
+        (s, ds)
+    }
+}
+
+impl Potential for Truncated {
+    #[inline]
+    fn energy(&self, r: f64) -> f64 {
+        if r >= self.cutoff {
+            return 0.0;
+        }
+        let energy = self.potential.energy(r);
+        match self.modifier {
+            Modifier::None => energy,
+            Modifier::PotentialShift => energy - self.energy_at_cutoff,
+            Modifier::ForceShift => {
+                energy - self.energy_at_cutoff + (r - self.cutoff) * self.force_at_cutoff
+            }
+            Modifier::Switch { r_switch } => energy * self.switch(r, r_switch).0,
+        }
+    }
+
+    #[inline]
+    fn force(&self, r: f64) -> f64 {
+        if r >= self.cutoff {
+            return 0.0;
+        }
+        let force = self.potential.force(r);
+        match self.modifier {
+            Modifier::None | Modifier::PotentialShift => force,
+            Modifier::ForceShift => force - self.force_at_cutoff,
+            Modifier::Switch { r_switch } => {
+                // F_s = -d(V * S)/dr = F * S - V * dS/dr
+                let (s, ds) = self.switch(r, r_switch);
+                force * s - self.potential.energy(r) * ds
+            }
+        }
+    }
+
+    /// The tail of the unmodified potential beyond the cutoff. Changes that the modifier makes
+    /// within the cutoff are not taken into account.
+    fn tail_energy(&self, cutoff: f64) -> f64 {
+        self.potential.tail_energy(cutoff)
+    }
+
+    /// The tail of the unmodified potential beyond the cutoff. Changes that the modifier makes
+    /// within the cutoff are not taken into account.
+    fn tail_virial(&self, cutoff: f64) -> f64 {
+        self.potential.tail_virial(cutoff)
+    }
+
+    fn unmodified(&self) -> Option<&dyn Potential> {
+        let potential = self.potential.as_ref();
+        Some(potential.unmodified().unwrap_or(potential))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the integral of `f(r) * 4 * π * r^2` from the cutoff to infinity, by Simpson's rule
+    /// over u = 1 / r, which maps the infinite range onto (0, 1 / r_c].
+    fn integrate_tail(f: impl Fn(f64) -> f64, cutoff: f64) -> f64 {
+        let intervals = 10000;
+        let h = 1.0 / cutoff / intervals as f64;
+        // dr = -du / u^2, so the integrand becomes 4 * π * f(1 / u) / u^4, which vanishes at u = 0
+        // for potentials that decay faster than r^-3.
+        let integrand = |u: f64| {
+            if u == 0.0 {
+                0.0
+            } else {
+                4.0 * PI * f(1.0 / u) / u.powi(4)
+            }
+        };
+        let sum: f64 = (0..=intervals)
+            .map(|i| {
+                let weight = match i {
+                    0 => 1.0,
+                    i if i == intervals => 1.0,
+                    i if i % 2 == 1 => 4.0,
+                    _ => 2.0,
+                };
+                weight * integrand(i as f64 * h)
+            })
+            .sum();
+        sum * h / 3.0
+    }
+
+    #[test]
+    fn lennard_jones_tail_matches_the_integral() {
+        let potential = LennardJones::from_molar(996.0, 0.34e-9);
+        for cutoff in [0.85e-9, 1.2e-9] {
+            let energy = integrate_tail(|r| potential.energy(r), cutoff);
+            let virial = integrate_tail(|r| r * potential.force(r), cutoff);
+            let tail_energy = potential.tail_energy(cutoff);
+            let tail_virial = potential.tail_virial(cutoff);
+            assert!(
+                (tail_energy - energy).abs() < 1e-9 * energy.abs(),
+                "{tail_energy} {energy}"
+            );
+            assert!(
+                (tail_virial - virial).abs() < 1e-9 * virial.abs(),
+                "{tail_virial} {virial}"
+            );
+        }
+    }
+}
@@ -1,6 +1,31 @@
-use std::{cmp::Ordering, num::ParseFloatError};
+use std::{cmp::Ordering, f64::consts::PI, num::ParseFloatError};
 
-use crate::{time::Time, vec3::Vec3};
+use crate::{
+    constraints::{
+        Constraint, ConstraintSolver, Lincs, Shake, DEFAULT_CONSTRAINT_TOLERANCE,
+        DEFAULT_LINCS_ITERATIONS, DEFAULT_LINCS_ORDER, DEFAULT_MAX_ITERATIONS,
+    },
+    electrostatics::{
+        Electrostatics, Ewald, Pme, ReactionField, Wolf, DEFAULT_EWALD_TOLERANCE,
+        DEFAULT_PME_ORDER, DEFAULT_PME_SPACING, DEFAULT_WOLF_ALPHA, ELEMENTARY_CHARGE,
+    },
+    integrator::DEFAULT_FRICTION,
+    minimize::{
+        ConjugateGradient, Lbfgs, SteepestDescent, DEFAULT_FORCE_TOLERANCE, DEFAULT_LBFGS_MEMORY,
+        DEFAULT_MINIMIZATION_STEP, DEFAULT_MINIMIZATION_STEPS,
+    },
+    potential::{Modifier, AVOGADRO},
+    species::{MixingRule, PairOverride, Species, DALTON},
+    thermostat::{DEFAULT_CHAIN_LENGTH, DEFAULT_COUPLING_PERIOD},
+    time::Time,
+    topology::{Angle, Bond, Dihedral, DihedralPotential, Improper, Molecule},
+    universe::{
+        Thermostat, DEFAULT_COUPLING_TIME, DEFAULT_CUTOFF, DEFAULT_EPSILON, DEFAULT_MASS,
+        DEFAULT_SIGMA, DEFAULT_SKIN,
+    },
+    vec3::Vec3,
+    water::WaterModel,
+};
 
 #[derive(Debug, Clone)]
 pub struct Recipe {
@@ -14,13 +39,60 @@ pub struct Recipe {
     /// Constant temperature (Kelvin).
     pub temperature: f64,
 
-    pub particles: usize,
+    /// Types of particles.
+    pub species: Vec<Species>,
+    /// Rule to derive the interactions between different species.
+    pub mixing: MixingRule,
+    /// Explicit interactions between pairs of species.
+    pub pairs: Vec<PairOverride>,
+    /// Number of particles of each species.
+    pub particles: Vec<usize>,
+    /// Types of molecules.
+    pub molecules: Vec<Molecule>,
+    /// Number of molecules of each type.
+    pub molecule_counts: Vec<usize>,
+    /// Scaling factor of the Lennard-Jones interactions between 1-4 pairs.
+    pub fudge_lj: f64,
+    /// Scaling factor of the Coulomb interactions between 1-4 pairs.
+    pub fudge_coulomb: f64,
+    /// Algorithm to satisfy the constraints.
+    pub constraint_solver: ConstraintSolver,
+    /// Scheme to advance the particles over a time step.
+    pub integrator: IntegratorSetting,
+    /// Temperature control.
+    pub thermostat: Thermostat,
+    /// Minimization of the energy to run instead of dynamics, if any.
+    pub minimize: Option<MinimizerSetting>,
 
     /// Vector specifying boundary (meter).
     pub boundary: Vec3,
+
+    /// Interaction cutoff (meter).
+    pub cutoff: f64,
+    /// Modification of the potentials near the cutoff.
+    pub modifier: Modifier,
+    /// Whether to correct the energy and pressure for the interactions beyond the cutoff.
+    pub dispersion_correction: bool,
+    /// Treatment of the interactions between charges.
+    pub electrostatics: Electrostatics,
+    /// Buffer distance added to the cutoff for the neighbour list (meter).
+    pub skin: f64,
+    /// Maximum number of steps between neighbour list rebuilds, if any.
+    pub rebuild_interval: Option<usize>,
 }
 
 impl Recipe {
+    /// Returns the total number of particles.
+    pub(crate) fn n_particles(&self) -> usize {
+        let in_molecules: usize = self
+            .molecules
+            .iter()
+            .zip(&self.molecule_counts)
+            .map(|(molecule, count)| molecule.species.len() * count)
+            .sum();
+        self.particles.iter().sum::<usize>() + in_molecules
+    }
+
     /// Returns the time from start to end as specified by the recipe.
     pub(crate) fn time(&self) -> Time {
         self.end - self.start
@@ -50,8 +122,31 @@ impl Recipe {
         let mut timestep = None;
         let mut snapshot = None;
         let mut temperature = None;
-        let mut particles = None;
+        let mut species = Vec::new();
+        let mut mixing = None;
+        let mut pairs = Vec::new();
+        let mut particles = Vec::new();
+        let mut molecules = Vec::new();
+        let mut bonds = Vec::new();
+        let mut angles = Vec::new();
+        let mut dihedrals = Vec::new();
+        let mut impropers = Vec::new();
+        let mut constraints = Vec::new();
+        let mut constraint_solver = None;
+        let mut water = None;
+        let mut integrator = None;
+        let mut thermostat = None;
+        let mut minimize = None;
+        let mut molecule_counts = Vec::new();
+        let mut fudge_lj = None;
+        let mut fudge_coulomb = None;
         let mut boundary = None;
+        let mut cutoff = None;
+        let mut modifier = None;
+        let mut dispersion_correction = None;
+        let mut electrostatics = None;
+        let mut skin = None;
+        let mut rebuild_interval = None;
         for line in src.lines() {
             let mut words = line.split_ascii_whitespace();
             match words.next() {
@@ -60,14 +155,204 @@ impl Recipe {
                 Some("end") => end = Some(parse_single_time(words.collect())?),
                 Some("snapshot") => snapshot = Some(parse_single_time(words.collect())?),
                 Some("timestep") => timestep = Some(parse_single_time(words.collect())?),
+                Some("integrator") => integrator = Some(parse_integrator(words.collect())?),
+                Some("minimize") => minimize = Some(parse_minimize(words.collect())?),
                 Some("temperature") => temperature = Some(parse_temperature(words.collect())?),
-                Some("particles") => particles = Some(parse_particles(words.collect())?),
+                Some("thermostat") => thermostat = Some(parse_thermostat(words.collect())?),
+                Some("species") => species.push(parse_species(words.collect())?),
+                Some("mixing") => mixing = Some(parse_mixing(words.collect())?),
+                Some("pair") => pairs.push(parse_pair(words.collect())?),
+                Some("particles") => particles.push(parse_particles(words.collect())?),
+                Some("molecule") => molecules.push(parse_molecule(words.collect())?),
+                Some("bond") => bonds.push(parse_bond(words.collect())?),
+                Some("angle") => angles.push(parse_angle(words.collect())?),
+                Some("dihedral") => dihedrals.push(parse_dihedral(words.collect())?),
+                Some("improper") => impropers.push(parse_improper(words.collect())?),
+                Some("constraint") => constraints.push(parse_constraint(words.collect())?),
+                Some("constraints") => {
+                    constraint_solver = Some(parse_constraint_solver(words.collect())?)
+                }
+                Some("water") => water = Some(parse_water(words.collect())?),
+                Some("molecules") => molecule_counts.push(parse_molecules(words.collect())?),
                 Some("boundary") => boundary = Some(parse_boundary(words.collect())?),
+                Some("cutoff") => cutoff = Some(parse_single_length(words.collect())?),
+                Some("modifier") => modifier = Some(parse_modifier(words.collect())?),
+                Some("electrostatics") => {
+                    electrostatics = Some(parse_electrostatics(words.collect())?)
+                }
+                Some("dispersioncorrection") => {
+                    dispersion_correction = Some(parse_switch(words.collect())?)
+                }
+                Some("fudge") => match words.next() {
+                    Some("lj") => fudge_lj = Some(parse_fudge(words.collect())?),
+                    Some("coulomb") => fudge_coulomb = Some(parse_fudge(words.collect())?),
+                    Some(_) => return Err(BibberParseError::UnknownSetting),
+                    None => return Err(BibberParseError::TooFewArguments),
+                },
+                Some("neighbourlist") => match words.next() {
+                    // Same as the top-level cutoff entry.
+                    Some("cutoff") => cutoff = Some(parse_single_length(words.collect())?),
+                    Some("skin") => skin = Some(parse_single_length(words.collect())?),
+                    Some("interval") => {
+                        rebuild_interval = Some(parse_rebuild_interval(words.collect())?)
+                    }
+                    Some(_) => return Err(BibberParseError::UnknownSetting),
+                    None => return Err(BibberParseError::TooFewArguments),
+                },
                 None => {}
-                _ => todo!(),
+                Some(_) => return Err(BibberParseError::UnknownKeyword),
             }
         }
 
+        // Without any species, all particles are of the same default species. This comes before
+        // the species of a water model, such that particles without a species are not its oxygens.
+        if species.is_empty() {
+            species.push(Species {
+                name: "DUM".to_string(),
+                mass: DEFAULT_MASS,
+                sigma: DEFAULT_SIGMA,
+                epsilon: DEFAULT_EPSILON / AVOGADRO,
+                charge: 0.0,
+            });
+        }
+        // A water model brings its own species.
+        let water_species = species.len();
+        if let Some(water) = water {
+            species.extend(water.species());
+        }
+        let species_index = |name: &str| {
+            species
+                .iter()
+                .position(|s| s.name == name)
+                .ok_or(BibberParseError::UnknownSpecies)
+        };
+        let pairs = pairs
+            .into_iter()
+            .map(|(a, b, sigma, epsilon)| {
+                Ok(PairOverride {
+                    species: (species_index(&a)?, species_index(&b)?),
+                    sigma,
+                    epsilon,
+                })
+            })
+            .collect::<Result<_, BibberParseError>>()?;
+        assert!(
+            !particles.is_empty() || !molecule_counts.is_empty(),
+            "recipe should specify particles or molecules"
+        );
+        let mut counts = vec![0; species.len()];
+        for (count, name) in particles {
+            // Particles without a species are of the first species.
+            let index = match name {
+                Some(name) => species_index(&name)?,
+                None => 0,
+            };
+            counts[index] += count;
+        }
+        let mut molecules = molecules
+            .into_iter()
+            .map(|(name, names)| {
+                Ok(Molecule {
+                    name,
+                    species: names
+                        .iter()
+                        .map(|name| species_index(name))
+                        .collect::<Result<_, _>>()?,
+                    bonds: Vec::new(),
+                    angles: Vec::new(),
+                    dihedrals: Vec::new(),
+                    impropers: Vec::new(),
+                    constraints: Vec::new(),
+                    settles: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>, BibberParseError>>()?;
+        if let Some(water) = water {
+            molecules.push(water.molecule(water_species, water_species + 1));
+        }
+        let molecule_index = |molecules: &[Molecule], name: &str| {
+            molecules
+                .iter()
+                .position(|m| m.name == name)
+                .ok_or(BibberParseError::UnknownMolecule)
+        };
+        // Returns the index of the molecule with some name, if the positions of the particles of
+        // an interaction are distinct and within that molecule.
+        let checked_index = |molecules: &[Molecule], name: &str, positions: &[usize]| {
+            let index = molecule_index(molecules, name)?;
+            let size = molecules[index].species.len();
+            for (n, &position) in positions.iter().enumerate() {
+                if position >= size || positions[..n].contains(&position) {
+                    return Err(BibberParseError::InvalidPosition);
+                }
+            }
+            Ok(index)
+        };
+        for (name, bond) in bonds {
+            let (i, j) = bond.particles;
+            let index = checked_index(&molecules, &name, &[i, j])?;
+            molecules[index].bonds.push(bond);
+        }
+        for (name, angle) in angles {
+            let (i, j, k) = angle.particles;
+            let index = checked_index(&molecules, &name, &[i, j, k])?;
+            molecules[index].angles.push(angle);
+        }
+        for (name, dihedral) in dihedrals {
+            let (i, j, k, l) = dihedral.particles;
+            let index = checked_index(&molecules, &name, &[i, j, k, l])?;
+            molecules[index].dihedrals.push(dihedral);
+        }
+        for (name, improper) in impropers {
+            let (i, j, k, l) = improper.particles;
+            let index = checked_index(&molecules, &name, &[i, j, k, l])?;
+            molecules[index].impropers.push(improper);
+        }
+        for (name, constraint) in constraints {
+            let (i, j) = constraint.particles;
+            let index = checked_index(&molecules, &name, &[i, j])?;
+            molecules[index].constraints.push(constraint);
+        }
+        let mut molecule_counts_by_index = vec![0; molecules.len()];
+        for (count, name) in molecule_counts {
+            molecule_counts_by_index[molecule_index(&molecules, &name)?] += count;
+        }
+
+        let boundary = boundary.expect("recipe should specify boundary");
+        let cutoff = cutoff.unwrap_or(DEFAULT_CUTOFF);
+        // Under the minimum-image convention, a particle only interacts with the closest image of
+        // every other particle. With a cutoff beyond half the box, some further images would be
+        // within the cutoff as well, but they would be silently ignored.
+        let smallest_edge = boundary.x.min(boundary.y).min(boundary.z);
+        if cutoff > 0.5 * smallest_edge {
+            return Err(BibberParseError::CutoffTooLarge);
+        }
+        // A switch at or beyond the cutoff would leave a plain truncated potential.
+        let modifier = modifier.unwrap_or_default();
+        if let Modifier::Switch { r_switch } = modifier {
+            if r_switch <= 0.0 || r_switch >= cutoff {
+                return Err(BibberParseError::InvalidValue);
+            }
+        }
+
+        let electrostatics = match electrostatics {
+            None | Some(ElectrostaticsSetting::None) => Electrostatics::None,
+            Some(ElectrostaticsSetting::Ewald { tolerance }) => {
+                Electrostatics::Ewald(Ewald::from_tolerance(cutoff, boundary, tolerance))
+            }
+            Some(ElectrostaticsSetting::Pme {
+                spacing,
+                order,
+                tolerance,
+            }) => Electrostatics::Pme(Pme::new(cutoff, boundary, tolerance, spacing, order)),
+            Some(ElectrostaticsSetting::ReactionField { epsilon_rf }) => {
+                Electrostatics::ReactionField(ReactionField { cutoff, epsilon_rf })
+            }
+            Some(ElectrostaticsSetting::Wolf { alpha }) => {
+                Electrostatics::Wolf(Wolf::new(cutoff, alpha))
+            }
+        };
+
         Ok(Self {
             title: title.expect("recipe should specify title"),
             start: start.expect("recipe should specify start"),
@@ -75,8 +360,27 @@ impl Recipe {
             snapshot: snapshot.expect("recipe should specify snapshot"),
             timestep: timestep.expect("recipe should specify timestep"),
             temperature: temperature.expect("recipe should specify temperature"),
-            particles: particles.expect("recipe should specify particles"),
-            boundary: boundary.expect("recipe should specify boundary"),
+            species,
+            mixing: mixing.unwrap_or_default(),
+            pairs,
+            particles: counts,
+            molecules,
+            molecule_counts: molecule_counts_by_index,
+            fudge_lj: fudge_lj.unwrap_or(1.0),
+            fudge_coulomb: fudge_coulomb.unwrap_or(1.0),
+            constraint_solver: constraint_solver.unwrap_or_default(),
+            integrator: integrator.unwrap_or_default(),
+            thermostat: thermostat.unwrap_or(Thermostat::Berendsen {
+                tau: DEFAULT_COUPLING_TIME,
+            }),
+            minimize,
+            boundary,
+            cutoff,
+            modifier,
+            dispersion_correction: dispersion_correction.unwrap_or(false),
+            electrostatics,
+            skin: skin.unwrap_or(DEFAULT_SKIN),
+            rebuild_interval,
         })
     }
 }
@@ -88,6 +392,13 @@ pub enum BibberParseError {
     NoUnit,
     UnknownUnit,
     InvalidUnit,
+    UnknownKeyword,
+    UnknownSetting,
+    UnknownSpecies,
+    UnknownMolecule,
+    InvalidPosition,
+    CutoffTooLarge,
+    InvalidValue,
     ParseFloatError(ParseFloatError),
 }
 
@@ -126,6 +437,70 @@ fn parse_arguments<const EXPECTED: usize>(
     Ok(args.try_into().unwrap())
 }
 
+/// Every unit that is understood by one of the unit parsers.
+const UNITS: &[&str] = &[
+    "km",
+    "m",
+    "dm",
+    "cm",
+    "mm",
+    "um",
+    "nm",
+    "pm",
+    "fm", // length
+    "s",
+    "ms",
+    "us",
+    "ns",
+    "ps",
+    "fs", // time
+    "/s",
+    "/ms",
+    "/us",
+    "/ns",
+    "/ps",
+    "/fs", // rate
+    "K",
+    "C", // temperature
+    "kg",
+    "g",
+    "u",
+    "Da", // mass
+    "J",
+    "kJ/mol",
+    "kcal/mol", // energy
+    "e",        // charge
+    "J/m2",
+    "kJ/mol/nm2", // bond force constant
+    "rad",
+    "deg", // angle
+    "J/rad2",
+    "kJ/mol/rad2", // angle force constant
+    "m2/s",
+    "cm2/s",
+    "um2/s",
+    "nm2/ns",
+    "nm2/ps", // diffusion coefficient
+    "N",
+    "kJ/mol/nm", // force
+    "/m",
+    "/cm",
+    "/mm",
+    "/um",
+    "/nm",
+    "/pm", // inverse length
+];
+
+/// Returns the error for a unit that is not appropriate for some value. That unit is invalid if
+/// it is understood for other values, and unknown otherwise.
+fn invalid_or_unknown(unit: &str) -> BibberParseError {
+    if UNITS.contains(&unit) {
+        BibberParseError::InvalidUnit
+    } else {
+        BibberParseError::UnknownUnit
+    }
+}
+
 // TODO: Fix horrible code duplication across parse_length, parse_time, parse_temperature.
 fn parse_length(s: &str) -> Result<f64, BibberParseError> {
     match s.split_once(':') {
@@ -142,10 +517,7 @@ fn parse_length(s: &str) -> Result<f64, BibberParseError> {
                 "nm" => 1e-9,
                 "pm" => 1e-12,
                 "fm" => 1e-15,
-                "s" | "ms" | "us" | "ns" | "ps" | "fs" | "K" | "C" => {
-                    return Err(BibberParseError::InvalidUnit)
-                }
-                _ => return Err(BibberParseError::UnknownUnit),
+                _ => return Err(invalid_or_unknown(unit)),
             };
             let meters = value * factor;
             Ok(meters)
@@ -165,16 +537,51 @@ fn parse_time(s: &str) -> Result<Time, BibberParseError> {
                 "ns" => Time::from_nanoseconds(value),
                 "ps" => Time::from_picoseconds(value),
                 "fs" => Time::from_femtoseconds(value),
-                "km" | "m" | "dm" | "cm" | "mm" | "um" | "nm" | "pm" | "fm" | "K" | "C" => {
-                    return Err(BibberParseError::InvalidUnit)
-                }
-                _ => return Err(BibberParseError::UnknownUnit),
+                _ => return Err(invalid_or_unknown(unit)),
             };
             Ok(time)
         }
     }
 }
 
+fn parse_rate(s: &str) -> Result<f64, BibberParseError> {
+    match s.split_once(':') {
+        None | Some((_, "")) => Err(BibberParseError::NoUnit),
+        Some((number, unit)) => {
+            let value: f64 = number.parse()?;
+            let period = match unit {
+                "/s" => Time::from_seconds(1.0),
+                "/ms" => Time::from_milliseconds(1.0),
+                "/us" => Time::from_microseconds(1.0),
+                "/ns" => Time::from_nanoseconds(1.0),
+                "/ps" => Time::from_picoseconds(1.0),
+                "/fs" => Time::from_femtoseconds(1.0),
+                _ => return Err(invalid_or_unknown(unit)),
+            };
+            Ok(value / period.seconds())
+        }
+    }
+}
+
+fn parse_inverse_length(s: &str) -> Result<f64, BibberParseError> {
+    match s.split_once(':') {
+        None | Some((_, "")) => Err(BibberParseError::NoUnit),
+        Some((number, unit)) => {
+            let value: f64 = number.parse()?;
+            let length = match unit {
+                "/m" => 1.0,
+                "/cm" => 1e-2,
+                "/mm" => 1e-3,
+                "/um" => 1e-6,
+                "/nm" => 1e-9,
+                "/pm" => 1e-12,
+                _ => return Err(invalid_or_unknown(unit)),
+            };
+            Ok(value / length)
+        }
+    }
+}
+
 fn parse_temperature_value(s: &str) -> Result<f64, BibberParseError> {
     match s.split_once(':') {
         None | Some((_, "")) => Err(BibberParseError::NoUnit),
@@ -183,9 +590,7 @@ fn parse_temperature_value(s: &str) -> Result<f64, BibberParseError> {
             let offset = match unit {
                 "K" => 0.0,
                 "C" => 273.15, // 0 C == -273.15 K
-                "km" | "m" | "dm" | "cm" | "mm" | "um" | "nm" | "pm" | "fm" | "s" | "ms" | "us"
-                | "ns" | "ps" | "fs" => return Err(BibberParseError::InvalidUnit),
-                _ => return Err(BibberParseError::UnknownUnit),
+                _ => return Err(invalid_or_unknown(unit)),
             };
             let kelvin = value - offset;
             Ok(kelvin)
@@ -193,12 +598,150 @@ fn parse_temperature_value(s: &str) -> Result<f64, BibberParseError> {
     }
 }
 
+fn parse_mass(s: &str) -> Result<f64, BibberParseError> {
+    match s.split_once(':') {
+        None | Some((_, "")) => Err(BibberParseError::NoUnit),
+        Some((number, unit)) => {
+            let value: f64 = number.parse()?;
+            let factor = match unit {
+                "kg" => 1.0,
+                "g" => 1e-3,
+                "u" | "Da" => DALTON,
+                _ => return Err(invalid_or_unknown(unit)),
+            };
+            let kilograms = value * factor;
+            Ok(kilograms)
+        }
+    }
+}
+
+fn parse_energy(s: &str) -> Result<f64, BibberParseError> {
+    match s.split_once(':') {
+        None | Some((_, "")) => Err(BibberParseError::NoUnit),
+        Some((number, unit)) => {
+            let value: f64 = number.parse()?;
+            let factor = match unit {
+                "J" => 1.0,
+                "kJ/mol" => 1e3 / AVOGADRO,
+                "kcal/mol" => 4.184e3 / AVOGADRO,
+                _ => return Err(invalid_or_unknown(unit)),
+            };
+            let joules = value * factor;
+            Ok(joules)
+        }
+    }
+}
+
+fn parse_charge(s: &str) -> Result<f64, BibberParseError> {
+    match s.split_once(':') {
+        None | Some((_, "")) => Err(BibberParseError::NoUnit),
+        Some((number, unit)) => {
+            let value: f64 = number.parse()?;
+            let factor = match unit {
+                "e" => ELEMENTARY_CHARGE,
+                _ => return Err(invalid_or_unknown(unit)),
+            };
+            let coulombs = value * factor;
+            Ok(coulombs)
+        }
+    }
+}
+
+fn parse_force(s: &str) -> Result<f64, BibberParseError> {
+    match s.split_once(':') {
+        None | Some((_, "")) => Err(BibberParseError::NoUnit),
+        Some((number, unit)) => {
+            let value: f64 = number.parse()?;
+            let factor = match unit {
+                "N" => 1.0,
+                "kJ/mol/nm" => 1e3 / AVOGADRO / 1e-9,
+                _ => return Err(invalid_or_unknown(unit)),
+            };
+            let newtons = value * factor;
+            Ok(newtons)
+        }
+    }
+}
+
+fn parse_force_constant(s: &str) -> Result<f64, BibberParseError> {
+    match s.split_once(':') {
+        None | Some((_, "")) => Err(BibberParseError::NoUnit),
+        Some((number, unit)) => {
+            let value: f64 = number.parse()?;
+            let factor = match unit {
+                "J/m2" => 1.0,
+                "kJ/mol/nm2" => 1e3 / AVOGADRO / 1e-18,
+                _ => return Err(invalid_or_unknown(unit)),
+            };
+            let joules_per_square_meter = value * factor;
+            Ok(joules_per_square_meter)
+        }
+    }
+}
+
+fn parse_diffusion(s: &str) -> Result<f64, BibberParseError> {
+    match s.split_once(':') {
+        None | Some((_, "")) => Err(BibberParseError::NoUnit),
+        Some((number, unit)) => {
+            let value: f64 = number.parse()?;
+            let factor = match unit {
+                "m2/s" => 1.0,
+                "cm2/s" => 1e-4,
+                "um2/s" => 1e-12,
+                "nm2/ns" => 1e-18 / 1e-9,
+                "nm2/ps" => 1e-18 / 1e-12,
+                _ => return Err(invalid_or_unknown(unit)),
+            };
+            let square_meters_per_second = value * factor;
+            Ok(square_meters_per_second)
+        }
+    }
+}
+
+fn parse_angle_value(s: &str) -> Result<f64, BibberParseError> {
+    match s.split_once(':') {
+        None | Some((_, "")) => Err(BibberParseError::NoUnit),
+        Some((number, unit)) => {
+            let value: f64 = number.parse()?;
+            let factor = match unit {
+                "rad" => 1.0,
+                "deg" => PI / 180.0,
+                _ => return Err(invalid_or_unknown(unit)),
+            };
+            let radians = value * factor;
+            Ok(radians)
+        }
+    }
+}
+
+fn parse_angle_force_constant(s: &str) -> Result<f64, BibberParseError> {
+    match s.split_once(':') {
+        None | Some((_, "")) => Err(BibberParseError::NoUnit),
+        Some((number, unit)) => {
+            let value: f64 = number.parse()?;
+            let factor = match unit {
+                "J/rad2" => 1.0,
+                "kJ/mol/rad2" => 1e3 / AVOGADRO,
+                _ => return Err(invalid_or_unknown(unit)),
+            };
+            let joules_per_square_radian = value * factor;
+            Ok(joules_per_square_radian)
+        }
+    }
+}
+
 /// Parse one time value.
 fn parse_single_time(arguments: Vec<&str>) -> Result<Time, BibberParseError> {
     let [time] = parse_arguments(arguments)?;
     parse_time(&time)
 }
 
+/// Parse one length value.
+fn parse_single_length(arguments: Vec<&str>) -> Result<f64, BibberParseError> {
+    let [length] = parse_arguments(arguments)?;
+    parse_length(&length)
+}
+
 /// Parse temperature.
 ///
 /// # Example
@@ -213,18 +756,744 @@ fn parse_temperature(arguments: Vec<&str>) -> Result<f64, BibberParseError> {
     parse_temperature_value(&temperature)
 }
 
-/// Parse number of particles.
+/// Parse a species with its mass, σ and ε, and optionally its charge.
 ///
 /// # Example
 ///
 /// ```
-/// // Line from which args are derived: boundary cubic 100:nm 100:nm 100:nm
-/// let args = vec!["100"];
-/// assert_eq!(parse_particles(args), 100)
+/// // Line from which args are derived: species Na 22.99:u 0.333:nm 0.0116:kJ/mol 1:e
+/// let args = vec!["Na", "22.99:u", "0.333:nm", "0.0116:kJ/mol", "1:e"];
+/// assert_eq!(parse_species(args).charge, ELEMENTARY_CHARGE)
+/// ```
+fn parse_species(arguments: Vec<&str>) -> Result<Species, BibberParseError> {
+    let [name, mass, sigma, epsilon, charge] = match arguments.len() {
+        4 => {
+            let [name, mass, sigma, epsilon] = parse_arguments(arguments)?;
+            [name, mass, sigma, epsilon, "0:e".to_string()]
+        }
+        _ => parse_arguments(arguments)?,
+    };
+    Ok(Species {
+        name,
+        mass: parse_mass(&mass)?,
+        sigma: parse_length(&sigma)?,
+        epsilon: parse_energy(&epsilon)?,
+        charge: parse_charge(&charge)?,
+    })
+}
+
+/// Parse the mixing rule.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: mixing geometric
+/// let args = vec!["geometric"];
+/// assert_eq!(parse_mixing(args), MixingRule::Geometric)
+/// ```
+fn parse_mixing(arguments: Vec<&str>) -> Result<MixingRule, BibberParseError> {
+    let [rule] = parse_arguments(arguments)?;
+    match rule.as_str() {
+        "lorentz-berthelot" => Ok(MixingRule::LorentzBerthelot),
+        "geometric" => Ok(MixingRule::Geometric),
+        _ => Err(BibberParseError::UnknownSetting),
+    }
+}
+
+/// Parse explicit Lennard-Jones parameters between two species, by name.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: pair A B 0.8:nm 1.5:kJ/mol
+/// let args = vec!["A", "B", "0.8:nm", "1.5:kJ/mol"];
+/// assert_eq!(parse_pair(args).2, 0.8e-9)
+/// ```
+fn parse_pair(arguments: Vec<&str>) -> Result<(String, String, f64, f64), BibberParseError> {
+    let [a, b, sigma, epsilon] = parse_arguments(arguments)?;
+    Ok((a, b, parse_length(&sigma)?, parse_energy(&epsilon)?))
+}
+
+/// Parse number of particles, optionally followed by the name of their species.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: particles 100 Ar
+/// let args = vec!["100", "Ar"];
+/// assert_eq!(parse_particles(args), (100, Some("Ar".to_string())))
+/// ```
+fn parse_particles(arguments: Vec<&str>) -> Result<(usize, Option<String>), BibberParseError> {
+    let (count, species) = match arguments.len() {
+        1 => {
+            let [count] = parse_arguments(arguments)?;
+            (count, None)
+        }
+        _ => {
+            let [count, species] = parse_arguments(arguments)?;
+            (count, Some(species))
+        }
+    };
+    Ok((count.parse::<f64>()? as usize, species))
+}
+
+/// Parse a molecule: its name, followed by the species of its particles.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: molecule N2 N N
+/// let args = vec!["N2", "N", "N"];
+/// assert_eq!(parse_molecule(args).1, vec!["N".to_string(), "N".to_string()])
+/// ```
+fn parse_molecule(arguments: Vec<&str>) -> Result<(String, Vec<String>), BibberParseError> {
+    match arguments.split_first() {
+        None | Some((_, [])) => Err(BibberParseError::TooFewArguments),
+        Some((name, species)) => Ok((
+            name.to_string(),
+            species.iter().map(|s| s.to_string()).collect(),
+        )),
+    }
+}
+
+/// Parse a harmonic bond within a molecule, between two of its particles by their 1-based
+/// position in the molecule, with its force constant and equilibrium length.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: bond N2 1 2 2e5:kJ/mol/nm2 0.11:nm
+/// let args = vec!["N2", "1", "2", "2e5:kJ/mol/nm2", "0.11:nm"];
+/// assert_eq!(parse_bond(args).1.particles, (0, 1))
+/// ```
+fn parse_bond(arguments: Vec<&str>) -> Result<(String, Bond), BibberParseError> {
+    let [molecule, i, j, k, r0] = parse_arguments(arguments)?;
+    Ok((
+        molecule,
+        Bond {
+            particles: (parse_position(&i)?, parse_position(&j)?),
+            k: parse_force_constant(&k)?,
+            r0: parse_length(&r0)?,
+        },
+    ))
+}
+
+/// Parse a harmonic angle within a molecule, between three of its particles by their 1-based
+/// position in the molecule, with its force constant and equilibrium angle.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: angle H2O 2 1 3 383:kJ/mol/rad2 109.47:deg
+/// let args = vec!["H2O", "2", "1", "3", "383:kJ/mol/rad2", "109.47:deg"];
+/// assert_eq!(parse_angle(args).1.particles, (1, 0, 2))
+/// ```
+fn parse_angle(arguments: Vec<&str>) -> Result<(String, Angle), BibberParseError> {
+    let [molecule, i, j, k, force_constant, theta0] = parse_arguments(arguments)?;
+    Ok((
+        molecule,
+        Angle {
+            particles: (
+                parse_position(&i)?,
+                parse_position(&j)?,
+                parse_position(&k)?,
+            ),
+            k: parse_angle_force_constant(&force_constant)?,
+            theta0: parse_angle_value(&theta0)?,
+        },
+    ))
+}
+
+/// Parse a proper dihedral within a molecule, between four of its particles by their 1-based
+/// position in the molecule, followed by its kind and parameters. A `periodic` dihedral has a
+/// force constant, phase and multiplicity, and a Ryckaert-Bellemans (`rb`) dihedral has six
+/// coefficients.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: dihedral C4 1 2 3 4 periodic 5.9:kJ/mol 0:deg 3
+/// let args = vec!["C4", "1", "2", "3", "4", "periodic", "5.9:kJ/mol", "0:deg", "3"];
+/// assert_eq!(
+///     parse_dihedral(args).1.potential,
+///     DihedralPotential::Periodic { k: 9.8e-21, phi0: 0.0, n: 3 }
+/// )
+/// ```
+fn parse_dihedral(arguments: Vec<&str>) -> Result<(String, Dihedral), BibberParseError> {
+    if arguments.len() < 6 {
+        return Err(BibberParseError::TooFewArguments);
+    }
+    let (head, parameters) = arguments.split_at(6);
+    let [molecule, i, j, k, l, kind] = parse_arguments(head.to_vec())?;
+    let potential = match kind.as_str() {
+        "periodic" => {
+            let [force_constant, phi0, n] = parse_arguments(parameters.to_vec())?;
+            DihedralPotential::Periodic {
+                k: parse_energy(&force_constant)?,
+                phi0: parse_angle_value(&phi0)?,
+                n: n.parse::<f64>()? as u32,
+            }
+        }
+        "rb" => {
+            let coefficients: [String; 6] = parse_arguments(parameters.to_vec())?;
+            let mut parsed = [0.0; 6];
+            for (c, coefficient) in parsed.iter_mut().zip(&coefficients) {
+                *c = parse_energy(coefficient)?;
+            }
+            DihedralPotential::RyckaertBellemans {
+                coefficients: parsed,
+            }
+        }
+        _ => return Err(BibberParseError::UnknownSetting),
+    };
+    Ok((
+        molecule,
+        Dihedral {
+            particles: (
+                parse_position(&i)?,
+                parse_position(&j)?,
+                parse_position(&k)?,
+                parse_position(&l)?,
+            ),
+            potential,
+        },
+    ))
+}
+
+/// Parse a harmonic improper dihedral within a molecule, between four of its particles by their
+/// 1-based position in the molecule, with its force constant and equilibrium angle.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: improper ALA 1 2 3 4 167:kJ/mol/rad2 0:deg
+/// let args = vec!["ALA", "1", "2", "3", "4", "167:kJ/mol/rad2", "0:deg"];
+/// assert_eq!(parse_improper(args).1.xi0, 0.0)
+/// ```
+fn parse_improper(arguments: Vec<&str>) -> Result<(String, Improper), BibberParseError> {
+    let [molecule, i, j, k, l, force_constant, xi0] = parse_arguments(arguments)?;
+    Ok((
+        molecule,
+        Improper {
+            particles: (
+                parse_position(&i)?,
+                parse_position(&j)?,
+                parse_position(&k)?,
+                parse_position(&l)?,
+            ),
+            k: parse_angle_force_constant(&force_constant)?,
+            xi0: parse_angle_value(&xi0)?,
+        },
+    ))
+}
+
+/// Parse a constraint within a molecule, between two of its particles by their 1-based position
+/// in the molecule, with its length.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: constraint H2O 1 2 0.1:nm
+/// let args = vec!["H2O", "1", "2", "0.1:nm"];
+/// assert_eq!(parse_constraint(args).1.length, 0.1e-9)
+/// ```
+fn parse_constraint(arguments: Vec<&str>) -> Result<(String, Constraint), BibberParseError> {
+    let [molecule, i, j, length] = parse_arguments(arguments)?;
+    Ok((
+        molecule,
+        Constraint {
+            particles: (parse_position(&i)?, parse_position(&j)?),
+            length: parse_length(&length)?,
+        },
+    ))
+}
+
+/// Parse the algorithm to satisfy the constraints, with an optional relative tolerance and
+/// maximum number of iterations for SHAKE, or an optional expansion order and number of
+/// iterations for LINCS.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: constraints shake 1e-6 100
+/// let args = vec!["shake", "1e-6", "100"];
+/// assert_eq!(
+///     parse_constraint_solver(args),
+///     ConstraintSolver::Shake(Shake { tolerance: 1e-6, max_iterations: 100 })
+/// )
+/// ```
+///
+/// ```
+/// // Line from which args are derived: constraints lincs 8 2
+/// let args = vec!["lincs", "8", "2"];
+/// assert_eq!(
+///     parse_constraint_solver(args),
+///     ConstraintSolver::Lincs(Lincs { order: 8, iterations: 2 })
+/// )
+/// ```
+fn parse_constraint_solver(arguments: Vec<&str>) -> Result<ConstraintSolver, BibberParseError> {
+    match arguments.first() {
+        Some(&"shake") => {
+            let (tolerance, max_iterations) = match arguments.len() {
+                1 => (DEFAULT_CONSTRAINT_TOLERANCE, DEFAULT_MAX_ITERATIONS),
+                2 => {
+                    let [_, tolerance] = parse_arguments(arguments)?;
+                    (tolerance.parse()?, DEFAULT_MAX_ITERATIONS)
+                }
+                _ => {
+                    let [_, tolerance, max_iterations] = parse_arguments(arguments)?;
+                    (tolerance.parse()?, max_iterations.parse::<f64>()? as usize)
+                }
+            };
+            Ok(ConstraintSolver::Shake(Shake {
+                tolerance,
+                max_iterations,
+            }))
+        }
+        Some(&"lincs") => {
+            let (order, iterations) = match arguments.len() {
+                1 => (DEFAULT_LINCS_ORDER, DEFAULT_LINCS_ITERATIONS),
+                2 => {
+                    let [_, order] = parse_arguments(arguments)?;
+                    (order.parse::<f64>()? as usize, DEFAULT_LINCS_ITERATIONS)
+                }
+                _ => {
+                    let [_, order, iterations] = parse_arguments(arguments)?;
+                    (
+                        order.parse::<f64>()? as usize,
+                        iterations.parse::<f64>()? as usize,
+                    )
+                }
+            };
+            Ok(ConstraintSolver::Lincs(Lincs { order, iterations }))
+        }
+        Some(_) => Err(BibberParseError::UnknownSetting),
+        None => Err(BibberParseError::TooFewArguments),
+    }
+}
+
+/// Parse the built-in model of rigid water.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: water spce
+/// let args = vec!["spce"];
+/// assert_eq!(parse_water(args), WaterModel::Spce)
+/// ```
+fn parse_water(arguments: Vec<&str>) -> Result<WaterModel, BibberParseError> {
+    let [model] = parse_arguments(arguments)?;
+    match model.as_str() {
+        "spce" => Ok(WaterModel::Spce),
+        "tip3p" => Ok(WaterModel::Tip3p),
+        _ => Err(BibberParseError::UnknownSetting),
+    }
+}
+
+/// Parse a 1-based position within a molecule into a 0-based index.
+fn parse_position(s: &str) -> Result<usize, BibberParseError> {
+    match s.parse::<f64>()? as usize {
+        0 => Err(BibberParseError::InvalidPosition),
+        position => Ok(position - 1),
+    }
+}
+
+/// Parse number of molecules, followed by the name of the molecule.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: molecules 100 N2
+/// let args = vec!["100", "N2"];
+/// assert_eq!(parse_molecules(args), (100, "N2".to_string()))
+/// ```
+fn parse_molecules(arguments: Vec<&str>) -> Result<(usize, String), BibberParseError> {
+    let [count, molecule] = parse_arguments(arguments)?;
+    Ok((count.parse::<f64>()? as usize, molecule))
+}
+
+/// Parse the scaling factor of the interactions between 1-4 pairs.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: fudge coulomb 0.8333
+/// let args = vec!["0.8333"];
+/// assert_eq!(parse_fudge(args), 0.8333)
+/// ```
+fn parse_fudge(arguments: Vec<&str>) -> Result<f64, BibberParseError> {
+    let [fudge] = parse_arguments(arguments)?;
+    Ok(fudge.parse()?)
+}
+
+/// Parse the modification of the potentials near the cutoff.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: modifier switch 0.9:nm
+/// let args = vec!["switch", "0.9:nm"];
+/// assert_eq!(parse_modifier(args), Modifier::Switch { r_switch: 0.9e-9 })
+/// ```
+fn parse_modifier(arguments: Vec<&str>) -> Result<Modifier, BibberParseError> {
+    match arguments.first() {
+        Some(&"switch") => {
+            let [_, r_switch] = parse_arguments(arguments)?;
+            Ok(Modifier::Switch {
+                r_switch: parse_length(&r_switch)?,
+            })
+        }
+        _ => {
+            let [modifier] = parse_arguments(arguments)?;
+            match modifier.as_str() {
+                "none" => Ok(Modifier::None),
+                "potential-shift" => Ok(Modifier::PotentialShift),
+                "force-shift" => Ok(Modifier::ForceShift),
+                _ => Err(BibberParseError::UnknownSetting),
+            }
+        }
+    }
+}
+
+/// Scheme to advance the particles over a time step, as specified in a recipe.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum IntegratorSetting {
+    /// Velocity Verlet.
+    #[default]
+    Verlet,
+    /// Leap-frog, with velocities half a step behind the positions.
+    LeapFrog,
+    /// Langevin dynamics with the BAOAB splitting, with its friction coefficient (1/s).
+    Langevin { friction: f64 },
+    /// Overdamped Brownian dynamics, with the diffusion coefficient of the particles (m^2/s).
+    Brownian { diffusion: f64 },
+}
+
+/// Parse the scheme to advance the particles over a time step.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: integrator verlet
+/// let args = vec!["verlet"];
+/// assert_eq!(parse_integrator(args), IntegratorSetting::Verlet)
+/// ```
+///
+/// ```
+/// // Line from which args are derived: integrator langevin 2:/ps
+/// let args = vec!["langevin", "2:/ps"];
+/// assert_eq!(parse_integrator(args), IntegratorSetting::Langevin { friction: 2e12 })
+/// ```
+///
+/// ```
+/// // Line from which args are derived: integrator brownian 1:um2/s
+/// let args = vec!["brownian", "1:um2/s"];
+/// assert_eq!(parse_integrator(args), IntegratorSetting::Brownian { diffusion: 1e-12 })
+/// ```
+fn parse_integrator(arguments: Vec<&str>) -> Result<IntegratorSetting, BibberParseError> {
+    match arguments.first() {
+        Some(&"langevin") => {
+            let friction = match arguments.len() {
+                1 => DEFAULT_FRICTION,
+                _ => {
+                    let [_, friction] = parse_arguments(arguments)?;
+                    parse_rate(&friction)?
+                }
+            };
+            Ok(IntegratorSetting::Langevin { friction })
+        }
+        Some(&"brownian") => {
+            let [_, diffusion] = parse_arguments(arguments)?;
+            Ok(IntegratorSetting::Brownian {
+                diffusion: parse_diffusion(&diffusion)?,
+            })
+        }
+        _ => {
+            let [integrator] = parse_arguments(arguments)?;
+            match integrator.as_str() {
+                "verlet" => Ok(IntegratorSetting::Verlet),
+                "leap-frog" => Ok(IntegratorSetting::LeapFrog),
+                _ => Err(BibberParseError::UnknownSetting),
+            }
+        }
+    }
+}
+
+/// Parse the temperature control, with the time constant of the coupling, and for Nosé-Hoover
+/// the length of the chain.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: thermostat berendsen 0.5:ps
+/// let args = vec!["berendsen", "0.5:ps"];
+/// assert_eq!(parse_thermostat(args), Thermostat::Berendsen { tau: 0.5e-12 })
+/// ```
+///
+/// ```
+/// // Line from which args are derived: thermostat nose-hoover 1:ps 5
+/// let args = vec!["nose-hoover", "1:ps", "5"];
+/// assert_eq!(
+///     parse_thermostat(args),
+///     Thermostat::NoseHoover {
+///         period: 1e-12,
+///         chain: 5
+///     }
+/// )
+/// ```
+fn parse_thermostat(arguments: Vec<&str>) -> Result<Thermostat, BibberParseError> {
+    match arguments.first() {
+        Some(&"none") => {
+            let [_] = parse_arguments(arguments)?;
+            Ok(Thermostat::None)
+        }
+        Some(&"berendsen") => {
+            let tau = match arguments.len() {
+                1 => DEFAULT_COUPLING_TIME,
+                _ => {
+                    let [_, tau] = parse_arguments(arguments)?;
+                    parse_time(&tau)?.seconds()
+                }
+            };
+            if tau <= 0.0 {
+                return Err(BibberParseError::InvalidValue);
+            }
+            Ok(Thermostat::Berendsen { tau })
+        }
+        Some(&"nose-hoover") => {
+            let (period, chain) = match arguments.len() {
+                1 => (DEFAULT_COUPLING_PERIOD, DEFAULT_CHAIN_LENGTH),
+                2 => {
+                    let [_, period] = parse_arguments(arguments)?;
+                    (parse_time(&period)?.seconds(), DEFAULT_CHAIN_LENGTH)
+                }
+                _ => {
+                    let [_, period, chain] = parse_arguments(arguments)?;
+                    // A negative length would wrap around when cast, so check it before.
+                    let chain = chain.parse::<f64>()?;
+                    if chain < 1.0 {
+                        return Err(BibberParseError::InvalidValue);
+                    }
+                    (parse_time(&period)?.seconds(), chain as usize)
+                }
+            };
+            if period <= 0.0 {
+                return Err(BibberParseError::InvalidValue);
+            }
+            Ok(Thermostat::NoseHoover { period, chain })
+        }
+        Some(_) => Err(BibberParseError::UnknownSetting),
+        None => Err(BibberParseError::TooFewArguments),
+    }
+}
+
+/// Algorithm to minimize the energy, as specified in a recipe.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MinimizerSetting {
+    SteepestDescent(SteepestDescent),
+    ConjugateGradient(ConjugateGradient),
+    Lbfgs(Lbfgs),
+}
+
+/// Parse the minimization of the energy, with the force tolerance and the number of steps.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: minimize steepest-descent 100:kJ/mol/nm 1000
+/// let args = vec!["steepest-descent", "100:kJ/mol/nm", "1000"];
+/// assert_eq!(
+///     parse_minimize(args),
+///     MinimizerSetting::SteepestDescent(SteepestDescent {
+///         tolerance: 100.0 * 1e3 / AVOGADRO / 1e-9,
+///         max_steps: 1000,
+///         step: DEFAULT_MINIMIZATION_STEP,
+///     })
+/// )
+/// ```
+///
+/// ```
+/// // Line from which args are derived: minimize l-bfgs
+/// let args = vec!["l-bfgs"];
+/// assert_eq!(parse_minimize(args), MinimizerSetting::Lbfgs(Lbfgs::default()))
+/// ```
+fn parse_minimize(arguments: Vec<&str>) -> Result<MinimizerSetting, BibberParseError> {
+    let Some(&algorithm) = arguments.first() else {
+        return Err(BibberParseError::TooFewArguments);
+    };
+    let (tolerance, max_steps) = match arguments.len() {
+        1 => (DEFAULT_FORCE_TOLERANCE, DEFAULT_MINIMIZATION_STEPS),
+        2 => {
+            let [_, tolerance] = parse_arguments(arguments)?;
+            (parse_force(&tolerance)?, DEFAULT_MINIMIZATION_STEPS)
+        }
+        _ => {
+            let [_, tolerance, max_steps] = parse_arguments(arguments)?;
+            (parse_force(&tolerance)?, max_steps.parse::<f64>()? as usize)
+        }
+    };
+    match algorithm {
+        "steepest-descent" => Ok(MinimizerSetting::SteepestDescent(SteepestDescent {
+            tolerance,
+            max_steps,
+            step: DEFAULT_MINIMIZATION_STEP,
+        })),
+        "conjugate-gradient" => Ok(MinimizerSetting::ConjugateGradient(ConjugateGradient {
+            tolerance,
+            max_steps,
+            step: DEFAULT_MINIMIZATION_STEP,
+        })),
+        "l-bfgs" => Ok(MinimizerSetting::Lbfgs(Lbfgs {
+            tolerance,
+            max_steps,
+            step: DEFAULT_MINIMIZATION_STEP,
+            memory: DEFAULT_LBFGS_MEMORY,
+        })),
+        _ => Err(BibberParseError::UnknownSetting),
+    }
+}
+
+/// Treatment of the interactions between charges, as specified in a recipe.
+///
+/// The parameters of the methods may depend on the cutoff and boundary, which are only known once
+/// the whole recipe has been read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ElectrostaticsSetting {
+    None,
+    Ewald {
+        tolerance: f64,
+    },
+    Pme {
+        spacing: f64,
+        order: usize,
+        tolerance: f64,
+    },
+    ReactionField {
+        epsilon_rf: f64,
+    },
+    Wolf {
+        alpha: f64,
+    },
+}
+
+/// Parse the treatment of the interactions between charges.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: electrostatics pme 0.1:nm 6
+/// let args = vec!["pme", "0.1:nm", "6"];
+/// assert_eq!(
+///     parse_electrostatics(args),
+///     ElectrostaticsSetting::Pme { spacing: 0.1e-9, order: 6, tolerance: 1e-5 }
+/// )
+/// ```
+fn parse_electrostatics(arguments: Vec<&str>) -> Result<ElectrostaticsSetting, BibberParseError> {
+    match arguments.first() {
+        Some(&"none") => {
+            let [_] = parse_arguments(arguments)?;
+            Ok(ElectrostaticsSetting::None)
+        }
+        Some(&"ewald") => {
+            let tolerance = match arguments.len() {
+                1 => DEFAULT_EWALD_TOLERANCE,
+                _ => {
+                    let [_, tolerance] = parse_arguments(arguments)?;
+                    tolerance.parse()?
+                }
+            };
+            Ok(ElectrostaticsSetting::Ewald { tolerance })
+        }
+        Some(&"pme") => {
+            let (spacing, order, tolerance) = match arguments.len() {
+                1 => (
+                    DEFAULT_PME_SPACING,
+                    DEFAULT_PME_ORDER,
+                    DEFAULT_EWALD_TOLERANCE,
+                ),
+                3 => {
+                    let [_, spacing, order] = parse_arguments(arguments)?;
+                    (
+                        parse_length(&spacing)?,
+                        parse_order(&order)?,
+                        DEFAULT_EWALD_TOLERANCE,
+                    )
+                }
+                _ => {
+                    let [_, spacing, order, tolerance] = parse_arguments(arguments)?;
+                    (
+                        parse_length(&spacing)?,
+                        parse_order(&order)?,
+                        tolerance.parse()?,
+                    )
+                }
+            };
+            Ok(ElectrostaticsSetting::Pme {
+                spacing,
+                order,
+                tolerance,
+            })
+        }
+        Some(&"reaction-field") => {
+            let [_, epsilon_rf] = parse_arguments(arguments)?;
+            Ok(ElectrostaticsSetting::ReactionField {
+                epsilon_rf: epsilon_rf.parse()?,
+            })
+        }
+        Some(&"wolf") => {
+            let alpha = match arguments.len() {
+                1 => DEFAULT_WOLF_ALPHA,
+                _ => {
+                    let [_, alpha] = parse_arguments(arguments)?;
+                    parse_inverse_length(&alpha)?
+                }
+            };
+            Ok(ElectrostaticsSetting::Wolf { alpha })
+        }
+        Some(_) => Err(BibberParseError::UnknownSetting),
+        None => Err(BibberParseError::TooFewArguments),
+    }
+}
+
+/// Parse the order of the PME B-splines, which must be at least 3.
+fn parse_order(s: &str) -> Result<usize, BibberParseError> {
+    let order = s.parse::<f64>()? as usize;
+    if order < 3 {
+        return Err(BibberParseError::InvalidValue);
+    }
+    Ok(order)
+}
+
+/// Parse a setting that is either on or off.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: dispersioncorrection on
+/// let args = vec!["on"];
+/// assert_eq!(parse_switch(args), true)
+/// ```
+fn parse_switch(arguments: Vec<&str>) -> Result<bool, BibberParseError> {
+    let [switch] = parse_arguments(arguments)?;
+    match switch.as_str() {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(BibberParseError::UnknownSetting),
+    }
+}
+
+/// Parse the maximum number of steps between neighbour list rebuilds.
+///
+/// # Example
+///
+/// ```
+/// // Line from which args are derived: neighbourlist interval 10
+/// let args = vec!["10"];
+/// assert_eq!(parse_rebuild_interval(args), 10)
 /// ```
-fn parse_particles(arguments: Vec<&str>) -> Result<usize, BibberParseError> {
-    let [particles] = parse_arguments(arguments)?;
-    Ok(particles.parse::<f64>()? as usize)
+fn parse_rebuild_interval(arguments: Vec<&str>) -> Result<usize, BibberParseError> {
+    let [interval] = parse_arguments(arguments)?;
+    Ok(interval.parse::<f64>()? as usize)
 }
 
 /// Parse specification of periodic boundary conditions.
@@ -246,3 +1515,129 @@ fn parse_boundary(arguments: Vec<&str>) -> Result<Vec3, BibberParseError> {
         parse_length(&z)?,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a recipe with the required entries, followed by some more lines.
+    fn recipe(lines: &str) -> Result<Recipe, BibberParseError> {
+        let src = format!(
+            "title test\nstart 0:ps\nend 1:ps\ntimestep 1:fs\nsnapshot 1:ps\ntemperature 300:K\n\
+             boundary cubic 3:nm 3:nm 3:nm\n{lines}"
+        );
+        Recipe::from_string(src)
+    }
+
+    #[test]
+    fn neighbourlist_cutoff_is_the_cutoff() {
+        let r = recipe("particles 10\nneighbourlist cutoff 0.9:nm").unwrap();
+        assert!((r.cutoff - 0.9e-9).abs() < 1e-20);
+        assert_eq!(
+            recipe("particles 10\nneighbourlist cutoff 2:nm").unwrap_err(),
+            BibberParseError::CutoffTooLarge
+        );
+    }
+
+    #[test]
+    fn unknown_keywords_are_reported() {
+        assert_eq!(
+            recipe("particles 10\nthermostatt berendsen").unwrap_err(),
+            BibberParseError::UnknownKeyword
+        );
+        // Empty lines are skipped.
+        assert!(recipe("particles 10\n\n  \n").is_ok());
+    }
+
+    #[test]
+    fn cutoff_beyond_half_the_box_is_too_large() {
+        let r = recipe("particles 10\ncutoff 1.5:nm").unwrap();
+        assert!((r.cutoff - 1.5e-9).abs() < 1e-20);
+        assert_eq!(
+            recipe("particles 10\ncutoff 1.6:nm").unwrap_err(),
+            BibberParseError::CutoffTooLarge
+        );
+    }
+
+    #[test]
+    fn switch_must_be_within_the_cutoff() {
+        let r = recipe("particles 10\ncutoff 1:nm\nmodifier switch 0.5:nm").unwrap();
+        assert_eq!(r.modifier, Modifier::Switch { r_switch: 0.5e-9 });
+        for r_switch in ["1:nm", "1.2:nm", "0:nm"] {
+            let lines = format!("particles 10\ncutoff 1:nm\nmodifier switch {r_switch}");
+            assert_eq!(recipe(&lines).unwrap_err(), BibberParseError::InvalidValue);
+        }
+    }
+
+    #[test]
+    fn wolf_damping_is_an_inverse_length() {
+        assert_eq!(
+            parse_electrostatics(vec!["wolf", "2:/nm"]).unwrap(),
+            ElectrostaticsSetting::Wolf { alpha: 2.0 / 1e-9 }
+        );
+        assert_eq!(
+            parse_electrostatics(vec!["wolf", "2"]).unwrap_err(),
+            BibberParseError::NoUnit
+        );
+        assert_eq!(
+            parse_electrostatics(vec!["pme", "0.1:nm", "2"]).unwrap_err(),
+            BibberParseError::InvalidValue
+        );
+    }
+
+    #[test]
+    fn berendsen_needs_a_positive_relaxation_time() {
+        assert_eq!(
+            parse_thermostat(vec!["berendsen", "0.5:ps"]).unwrap(),
+            Thermostat::Berendsen { tau: 0.5e-12 }
+        );
+        for tau in ["0:ps", "-0.1:ps"] {
+            assert_eq!(
+                parse_thermostat(vec!["berendsen", tau]).unwrap_err(),
+                BibberParseError::InvalidValue
+            );
+        }
+    }
+
+    #[test]
+    fn nose_hoover_needs_a_positive_period_and_chain() {
+        assert_eq!(
+            parse_thermostat(vec!["nose-hoover", "1:ps", "5"]).unwrap(),
+            Thermostat::NoseHoover {
+                period: 1e-12,
+                chain: 5
+            }
+        );
+        for arguments in [
+            vec!["nose-hoover", "1:ps", "0"],
+            vec!["nose-hoover", "1:ps", "-1"],
+            vec!["nose-hoover", "0:ps"],
+            vec!["nose-hoover", "-1:ps", "3"],
+        ] {
+            assert_eq!(
+                parse_thermostat(arguments).unwrap_err(),
+                BibberParseError::InvalidValue
+            );
+        }
+    }
+
+    #[test]
+    fn particles_without_a_species_are_not_water() {
+        let r = recipe("water spce\nparticles 10\nmolecules 5 SOL").unwrap();
+        let names: Vec<_> = r.species.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["DUM", "OW", "HW"]);
+        assert_eq!(r.particles, [10, 0, 0]);
+        assert_eq!(r.molecules[0].species, [1, 2, 2]);
+        // The system is neutral.
+        let charge: f64 = (r.particles.iter().enumerate())
+            .map(|(index, &count)| count as f64 * r.species[index].charge)
+            .chain(
+                r.molecules[0]
+                    .species
+                    .iter()
+                    .map(|&s| 5.0 * r.species[s].charge),
+            )
+            .sum();
+        assert!(charge.abs() < 1e-6 * ELEMENTARY_CHARGE, "{charge}");
+    }
+}
@@ -0,0 +1,154 @@
+use crate::potential::{LennardJones, PairTable};
+
+/// Atomic mass constant (kg).
+pub const DALTON: f64 = 1.66053906660e-27;
+
+/// A type of particle with its own mass and Lennard-Jones parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Species {
+    pub name: String,
+    /// Mass in kg.
+    pub mass: f64,
+    /// Distance at which the Lennard-Jones potential between two of these particles crosses zero
+    /// (meter).
+    pub sigma: f64,
+    /// Depth of the Lennard-Jones potential well between two of these particles (J).
+    pub epsilon: f64,
+    /// Charge in Coulomb.
+    pub charge: f64,
+}
+
+impl Species {
+    /// Returns the Lennard-Jones potential between two particles of this species.
+    pub fn lennard_jones(&self) -> LennardJones {
+        LennardJones::new(self.epsilon, self.sigma)
+    }
+}
+
+/// Rule to derive the Lennard-Jones parameters between two different species from their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MixingRule {
+    /// σ_ab = (σ_a + σ_b) / 2 and ε_ab = sqrt(ε_a * ε_b)
+    #[default]
+    LorentzBerthelot,
+    /// σ_ab = sqrt(σ_a * σ_b) and ε_ab = sqrt(ε_a * ε_b)
+    Geometric,
+}
+
+impl MixingRule {
+    /// Returns the Lennard-Jones potential between particles of two species.
+    pub fn mix(&self, a: &Species, b: &Species) -> LennardJones {
+        let sigma = match self {
+            MixingRule::LorentzBerthelot => 0.5 * (a.sigma + b.sigma),
+            MixingRule::Geometric => (a.sigma * b.sigma).sqrt(),
+        };
+        let epsilon = (a.epsilon * b.epsilon).sqrt();
+        LennardJones::new(epsilon, sigma)
+    }
+}
+
+/// Explicit Lennard-Jones parameters between two species, which take precedence over the mixing
+/// rule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairOverride {
+    /// Indices of the two species.
+    pub species: (usize, usize),
+    /// Distance at which the potential crosses zero (meter).
+    pub sigma: f64,
+    /// Depth of the potential well (J).
+    pub epsilon: f64,
+}
+
+/// Returns the Lennard-Jones potentials between all pairs of species.
+pub fn lennard_jones_table(
+    species: &[Species],
+    mixing: MixingRule,
+    overrides: &[PairOverride],
+) -> PairTable {
+    PairTable::new(species.len(), |a, b| {
+        let pair_override = overrides.iter().rev().find(|o| {
+            let (c, d) = o.species;
+            (c, d) == (a, b) || (d, c) == (a, b)
+        });
+        let potential = match pair_override {
+            Some(o) => LennardJones::new(o.epsilon, o.sigma),
+            None if a == b => species[a].lennard_jones(),
+            None => mixing.mix(&species[a], &species[b]),
+        };
+        Box::new(potential)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::potential::Potential;
+
+    fn species(name: &str, sigma: f64, epsilon: f64) -> Species {
+        Species {
+            name: name.to_string(),
+            mass: 12.0 * DALTON,
+            sigma,
+            epsilon,
+            charge: 0.0,
+        }
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() <= 1e-12 * b.abs(), "{a} {b}");
+    }
+
+    #[test]
+    fn mixing_rules_combine_sigma_and_epsilon() {
+        let (a, b) = (species("A", 0.3e-9, 1e-21), species("B", 0.4e-9, 4e-21));
+        let lorentz_berthelot = MixingRule::LorentzBerthelot.mix(&a, &b);
+        assert_close(lorentz_berthelot.sigma, 0.35e-9);
+        assert_close(lorentz_berthelot.epsilon, 2e-21);
+        let geometric = MixingRule::Geometric.mix(&a, &b);
+        assert_close(geometric.sigma, (0.12f64).sqrt() * 1e-9);
+        assert_close(geometric.epsilon, 2e-21);
+    }
+
+    #[test]
+    fn table_holds_every_pair_of_species() {
+        let all = [
+            species("A", 0.30e-9, 1e-21),
+            species("B", 0.35e-9, 2e-21),
+            species("C", 0.40e-9, 3e-21),
+            species("D", 0.45e-9, 4e-21),
+        ];
+        let table = lennard_jones_table(&all, MixingRule::LorentzBerthelot, &[]);
+        assert_eq!(table.species(), 4);
+        let r = 0.5e-9;
+        for a in 0..all.len() {
+            for b in 0..all.len() {
+                let expected = MixingRule::LorentzBerthelot.mix(&all[a], &all[b]);
+                assert_close(table.get(a, b).energy(r), expected.energy(r));
+            }
+        }
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_mixing() {
+        let all = [
+            species("A", 0.30e-9, 1e-21),
+            species("B", 0.35e-9, 2e-21),
+            species("C", 0.40e-9, 3e-21),
+        ];
+        let r = 0.5e-9;
+        let overridden = LennardJones::new(5e-21, 0.2e-9);
+        for pair in [(0, 2), (2, 0)] {
+            let overrides = [PairOverride {
+                species: pair,
+                sigma: overridden.sigma,
+                epsilon: overridden.epsilon,
+            }];
+            let table = lennard_jones_table(&all, MixingRule::Geometric, &overrides);
+            assert_close(table.get(0, 2).energy(r), overridden.energy(r));
+            assert_close(table.get(2, 0).energy(r), overridden.energy(r));
+            // The other pairs are still mixed.
+            let mixed = MixingRule::Geometric.mix(&all[0], &all[1]);
+            assert_close(table.get(1, 0).energy(r), mixed.energy(r));
+        }
+    }
+}
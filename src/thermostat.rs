@@ -0,0 +1,152 @@
+use std::f64::consts::PI;
+
+use crate::universe::BOLTZMANN;
+
+/// Period of the oscillations of the temperature under a Nosé-Hoover thermostat if none is given
+/// (seconds).
+pub const DEFAULT_COUPLING_PERIOD: f64 = 0.5e-12;
+/// Number of thermostats in a Nosé-Hoover chain if none is given.
+pub const DEFAULT_CHAIN_LENGTH: usize = 3;
+
+/// State of a chain of Nosé-Hoover thermostats, which extends the system with a position ξ and
+/// velocity v_ξ for every thermostat, such that it samples the canonical ensemble.
+///
+/// The first thermostat couples to the kinetic energy of the particles, and every next one to the
+/// kinetic energy of the one before it, such that even stiff systems are sampled ergodically.
+///
+/// ```
+/// dv/dt    = F / m - v_ξ1 * v
+/// dv_ξ1/dt = (2 * E_kin - N_df * k_B * T) / Q_1 - v_ξ2 * v_ξ1
+/// dv_ξj/dt = (Q_j-1 * v_ξj-1^2 - k_B * T) / Q_j - v_ξj+1 * v_ξj
+/// dξj/dt   = v_ξj
+/// ```
+///
+/// The masses follow from the period τ of the oscillations of the temperature, as in GROMACS,
+///
+/// ```
+/// Q_1 = N_df * k_B * T * τ^2 / (4 * π^2)
+/// Q_j = k_B * T * τ^2 / (4 * π^2)
+/// ```
+///
+/// See Martyna, G. J., Klein, M. L. & Tuckerman, M. (1992). J. Chem. Phys. 97, 2635.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NoseHooverChain {
+    /// Positions ξ of the thermostats (dimensionless).
+    positions: Vec<f64>,
+    /// Velocities v_ξ of the thermostats (1/s).
+    velocities: Vec<f64>,
+}
+
+impl NoseHooverChain {
+    /// Creates a chain of `length` thermostats at rest.
+    pub fn new(length: usize) -> Self {
+        Self {
+            positions: vec![0.0; length],
+            velocities: vec![0.0; length],
+        }
+    }
+
+    /// Returns the masses Q of the thermostats (J⋅s²), for `dof` degrees of freedom at the
+    /// `temperature` (Kelvin) with the `period` (seconds).
+    fn masses(&self, dof: f64, temperature: f64, period: f64) -> Vec<f64> {
+        let q = BOLTZMANN * temperature * period * period / (4.0 * PI * PI);
+        (0..self.velocities.len())
+            .map(|j| if j == 0 { dof * q } else { q })
+            .collect()
+    }
+
+    /// Propagates the chain over a time `dt` (seconds), under the kinetic energy (Joules) of the
+    /// particles, as in the Trotter factorization of Martyna, Tuckerman, Tobias & Klein (1996).
+    ///
+    /// Returns the factor by which to scale the velocities of the particles.
+    pub fn propagate(
+        &mut self,
+        kinetic: f64,
+        dof: f64,
+        temperature: f64,
+        period: f64,
+        dt: f64,
+    ) -> f64 {
+        let length = self.velocities.len();
+        if length == 0 {
+            return 1.0;
+        }
+        let kt = BOLTZMANN * temperature;
+        let q = self.masses(dof, temperature, period);
+        let v = &mut self.velocities;
+        // Each thermostat is kicked by its force over half of dt, in between two damping halves by
+        // the next thermostat.
+        let kick = |v: &mut [f64], j: usize, kinetic: f64| {
+            let force = if j == 0 {
+                (2.0 * kinetic - dof * kt) / q[0]
+            } else {
+                (q[j - 1] * v[j - 1] * v[j - 1] - kt) / q[j]
+            };
+            let damping = match v.get(j + 1) {
+                Some(next) => (-0.25 * dt * next).exp(),
+                None => 1.0,
+            };
+            v[j] = (v[j] * damping + 0.5 * dt * force) * damping;
+        };
+
+        // Down the chain, then scale the velocities of the particles, and back up the chain.
+        for j in (0..length).rev() {
+            kick(v, j, kinetic);
+        }
+        let scale = (-dt * v[0]).exp();
+        let kinetic = kinetic * scale * scale;
+        for (position, velocity) in self.positions.iter_mut().zip(v.iter()) {
+            *position += dt * velocity;
+        }
+        for j in 0..length {
+            kick(v, j, kinetic);
+        }
+        scale
+    }
+
+    /// Returns the energy of the thermostats (Joules), which together with the energy of the
+    /// particles is conserved.
+    ///
+    /// ```
+    /// E = Σ 1/2 * Q_j * v_ξj^2 + N_df * k_B * T * ξ_1 + Σ_j>1 k_B * T * ξ_j
+    /// ```
+    pub fn energy(&self, dof: f64, temperature: f64, period: f64) -> f64 {
+        let kt = BOLTZMANN * temperature;
+        let q = self.masses(dof, temperature, period);
+        let kinetic: f64 = (q.iter().zip(&self.velocities))
+            .map(|(q, v)| 0.5 * q * v * v)
+            .sum();
+        let potential: f64 = (self.positions.iter().enumerate())
+            .map(|(j, position)| if j == 0 { dof } else { 1.0 } * kt * position)
+            .sum();
+        kinetic + potential
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_exchanges_energy_with_the_particles() {
+        // Without forces, the kinetic energy of the particles only changes by the thermostats, and
+        // its sum with the energy of the chain is conserved.
+        let (dof, temperature, period, dt) = (30.0, 300.0, DEFAULT_COUPLING_PERIOD, 1e-15);
+        let mut kinetic = 0.5 * dof * BOLTZMANN * 600.0;
+        let mut chain = NoseHooverChain::new(4);
+        let initial = kinetic;
+        let mut coldest = kinetic;
+        for _ in 0..2000 {
+            let scale = chain.propagate(kinetic, dof, temperature, period, dt);
+            kinetic *= scale * scale;
+            coldest = coldest.min(kinetic);
+            let total = kinetic + chain.energy(dof, temperature, period);
+            assert!(
+                (total - initial).abs() < 1e-4 * initial,
+                "{total} {initial}"
+            );
+        }
+        // The chain has cooled the particles below the set point at some point.
+        assert!(coldest < 0.5 * dof * BOLTZMANN * temperature, "{coldest}");
+    }
+}
@@ -0,0 +1,654 @@
+use std::f64::consts::PI;
+
+use crate::constraints::{Constraint, Settle};
+use crate::universe::{minimum_image, Particle};
+use crate::vec3::Vec3;
+
+/// A harmonic bond between two particles.
+///
+/// ```
+/// V(r) = 1/2 * k * (r - r_0)^2
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bond {
+    /// Indices of the bonded particles.
+    pub particles: (usize, usize),
+    /// Force constant (J/m^2).
+    pub k: f64,
+    /// Equilibrium length (meter).
+    pub r0: f64,
+}
+
+impl Bond {
+    /// Returns this bond with the indices of its particles shifted by some offset.
+    fn offset(self, offset: usize) -> Self {
+        let (i, j) = self.particles;
+        Self {
+            particles: (i + offset, j + offset),
+            ..self
+        }
+    }
+}
+
+/// A harmonic angle between three particles, of which the second is in the middle.
+///
+/// ```
+/// V(θ) = 1/2 * k * (θ - θ_0)^2
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Angle {
+    /// Indices of the particles.
+    pub particles: (usize, usize, usize),
+    /// Force constant (J/rad^2).
+    pub k: f64,
+    /// Equilibrium angle (rad).
+    pub theta0: f64,
+}
+
+impl Angle {
+    /// Returns this angle with the indices of its particles shifted by some offset.
+    fn offset(self, offset: usize) -> Self {
+        let (i, j, k) = self.particles;
+        Self {
+            particles: (i + offset, j + offset, k + offset),
+            ..self
+        }
+    }
+}
+
+/// A proper dihedral between four consecutively bonded particles.
+///
+/// The dihedral angle φ is the angle between the plane through the first three particles and the
+/// plane through the last three particles. It is zero in the cis conformation and π in the trans
+/// conformation (IUPAC).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dihedral {
+    /// Indices of the particles.
+    pub particles: (usize, usize, usize, usize),
+    pub potential: DihedralPotential,
+}
+
+impl Dihedral {
+    /// Returns this dihedral with the indices of its particles shifted by some offset.
+    fn offset(self, offset: usize) -> Self {
+        let (i, j, k, l) = self.particles;
+        Self {
+            particles: (i + offset, j + offset, k + offset, l + offset),
+            ..self
+        }
+    }
+}
+
+/// The potential energy of a proper dihedral as a function of the dihedral angle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DihedralPotential {
+    /// A periodic dihedral with a force constant `k` (J), phase `phi0` (rad) and multiplicity
+    /// `n`.
+    ///
+    /// ```
+    /// V(φ) = k * (1 + cos(n * φ - φ_0))
+    /// ```
+    Periodic { k: f64, phi0: f64, n: u32 },
+    /// A Ryckaert-Bellemans dihedral with coefficients C_0 to C_5 (J), in the polymer convention
+    /// where ψ = φ - π is zero in the trans conformation.
+    ///
+    /// ```
+    /// V(ψ) = Σ_{n=0}^{5} C_n * cos(ψ)^n
+    /// ```
+    RyckaertBellemans { coefficients: [f64; 6] },
+}
+
+impl DihedralPotential {
+    /// Returns the energy (J) and its derivative to the dihedral angle (J/rad) at some dihedral
+    /// angle `phi` (rad).
+    fn energy(&self, phi: f64) -> (f64, f64) {
+        match *self {
+            DihedralPotential::Periodic { k, phi0, n } => {
+                let n = n as f64;
+                let argument = n * phi - phi0;
+                (k * (1.0 + argument.cos()), -k * n * argument.sin())
+            }
+            DihedralPotential::RyckaertBellemans { coefficients } => {
+                let psi = phi - PI;
+                let (cos, sin) = (psi.cos(), psi.sin());
+                // Sum V and dV/dcos(ψ) by Horner's method, from the highest power down.
+                let mut energy = 0.0;
+                let mut derivative = 0.0;
+                for &c in coefficients.iter().rev() {
+                    derivative = derivative * cos + energy;
+                    energy = energy * cos + c;
+                }
+                // dV/dφ = dV/dψ = dV/dcos(ψ) * -sin(ψ)
+                (energy, -derivative * sin)
+            }
+        }
+    }
+}
+
+/// A harmonic improper dihedral, which keeps a group of four particles planar or in some
+/// chirality.
+///
+/// The improper angle ξ is the dihedral angle between the planes through the first three and the
+/// last three particles.
+///
+/// ```
+/// V(ξ) = 1/2 * k * (ξ - ξ_0)^2
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Improper {
+    /// Indices of the particles.
+    pub particles: (usize, usize, usize, usize),
+    /// Force constant (J/rad^2).
+    pub k: f64,
+    /// Equilibrium improper angle (rad).
+    pub xi0: f64,
+}
+
+impl Improper {
+    /// Returns this improper dihedral with the indices of its particles shifted by some offset.
+    fn offset(self, offset: usize) -> Self {
+        let (i, j, k, l) = self.particles;
+        Self {
+            particles: (i + offset, j + offset, k + offset, l + offset),
+            ..self
+        }
+    }
+}
+
+/// A template for a molecule: a number of particles, connected by bonds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Molecule {
+    pub name: String,
+    /// Indices of the species of the particles in the molecule.
+    pub species: Vec<usize>,
+    /// Bonds between the particles, indexed within the molecule.
+    pub bonds: Vec<Bond>,
+    /// Angles between the particles, indexed within the molecule.
+    pub angles: Vec<Angle>,
+    /// Proper dihedrals between the particles, indexed within the molecule.
+    pub dihedrals: Vec<Dihedral>,
+    /// Improper dihedrals between the particles, indexed within the molecule.
+    pub impropers: Vec<Improper>,
+    /// Constrained bonds between the particles, indexed within the molecule.
+    pub constraints: Vec<Constraint>,
+    /// Rigid water-like triangles of particles, indexed within the molecule.
+    pub settles: Vec<Settle>,
+}
+
+/// The bonded structure of the system.
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    pub(crate) bonds: Vec<Bond>,
+    pub(crate) angles: Vec<Angle>,
+    pub(crate) dihedrals: Vec<Dihedral>,
+    pub(crate) impropers: Vec<Improper>,
+    pub(crate) constraints: Vec<Constraint>,
+    pub(crate) settles: Vec<Settle>,
+    /// For every particle, the sorted indices of the particles with a higher index that it has no
+    /// nonbonded interactions with.
+    exclusions: Vec<Vec<usize>>,
+    /// Pairs of particles that are three bonds apart (1-4 pairs). Their nonbonded interactions
+    /// are excluded, and replaced by scaled interactions.
+    pub(crate) pairs: Vec<(usize, usize)>,
+}
+
+impl Topology {
+    /// Creates a new, empty [`Topology`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a molecule, of which the first particle has index `offset` in the system.
+    ///
+    /// The nonbonded interactions between particles that are one or two bonds apart (1-2 and 1-3
+    /// pairs) are excluded. Those between particles that are three bonds apart (1-4 pairs) are
+    /// excluded as well, but the pairs are kept to be treated separately. Constraints and the
+    /// sides of rigid triangles count as bonds.
+    pub fn add_molecule(&mut self, molecule: &Molecule, offset: usize) {
+        for &bond in &molecule.bonds {
+            self.add_bond(bond.offset(offset));
+        }
+
+        let mut bonded = vec![Vec::new(); molecule.species.len()];
+        let bonds = molecule.bonds.iter().map(|bond| bond.particles);
+        let settles = molecule.settles.iter().flat_map(Settle::constraints);
+        let constraints = molecule.constraints.iter().copied().chain(settles);
+        for (i, j) in bonds.chain(constraints.map(|c| c.particles)) {
+            bonded[i].push(j);
+            bonded[j].push(i);
+        }
+        for start in 0..molecule.species.len() {
+            // Walk the bond graph breadth-first, up to three bonds away. A particle that can be
+            // reached in different ways (in a ring) is counted at its shortest distance.
+            let mut visited = vec![start];
+            let mut frontier = vec![start];
+            for distance in 1..=3 {
+                let mut next = Vec::new();
+                for &i in &frontier {
+                    for &j in &bonded[i] {
+                        if visited.contains(&j) {
+                            continue;
+                        }
+                        visited.push(j);
+                        next.push(j);
+                        // Every pair is found from both of its ends, so only take it once.
+                        if start < j {
+                            self.exclude(start + offset, j + offset);
+                            if distance == 3 {
+                                self.pairs.push((start + offset, j + offset));
+                            }
+                        }
+                    }
+                }
+                frontier = next;
+            }
+        }
+
+        self.angles
+            .extend(molecule.angles.iter().map(|angle| angle.offset(offset)));
+        self.dihedrals.extend(
+            molecule
+                .dihedrals
+                .iter()
+                .map(|dihedral| dihedral.offset(offset)),
+        );
+        self.impropers.extend(
+            molecule
+                .impropers
+                .iter()
+                .map(|improper| improper.offset(offset)),
+        );
+        self.constraints.extend(
+            molecule
+                .constraints
+                .iter()
+                .map(|constraint| constraint.offset(offset)),
+        );
+        self.settles
+            .extend(molecule.settles.iter().map(|settle| settle.offset(offset)));
+    }
+
+    /// Add a bond. Bonded particles do not have nonbonded interactions.
+    pub fn add_bond(&mut self, bond: Bond) {
+        let (i, j) = bond.particles;
+        self.bonds.push(bond);
+        self.exclude(i, j);
+    }
+
+    /// Exclude the nonbonded interactions between two particles.
+    fn exclude(&mut self, i: usize, j: usize) {
+        let (i, j) = if i < j { (i, j) } else { (j, i) };
+        if self.exclusions.len() <= i {
+            self.exclusions.resize(i + 1, Vec::new());
+        }
+        let excluded = &mut self.exclusions[i];
+        if let Err(position) = excluded.binary_search(&j) {
+            excluded.insert(position, j);
+        }
+    }
+
+    /// Returns whether the nonbonded interactions between two particles are excluded.
+    #[inline]
+    pub fn excludes(&self, i: usize, j: usize) -> bool {
+        let (i, j) = if i < j { (i, j) } else { (j, i) };
+        self.exclusions
+            .get(i)
+            .is_some_and(|excluded| excluded.binary_search(&j).is_ok())
+    }
+
+    /// Returns every excluded pair once.
+    pub fn exclusions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.exclusions
+            .iter()
+            .enumerate()
+            .flat_map(|(i, excluded)| excluded.iter().map(move |&j| (i, j)))
+    }
+
+    /// Adds the bonded forces, and returns the bonded energy (J) and virial (J).
+    ///
+    /// Bonded interactions may cross the periodic boundary, so the vectors between their
+    /// particles are taken according to the minimum-image convention.
+    pub fn forces(
+        &self,
+        particles: &[Particle],
+        boundary: Vec3,
+        forces: &mut [Vec3],
+    ) -> (f64, f64) {
+        let vector =
+            |i: usize, j: usize| minimum_image(particles[i].pos - particles[j].pos, boundary);
+        let mut energy = 0.0;
+        let mut virial = 0.0;
+
+        for bond in &self.bonds {
+            let (i, j) = bond.particles;
+            let r = vector(i, j);
+            let distance = r.norm();
+            let stretch = distance - bond.r0;
+            // F(r) = -dV/dr = -k * (r - r_0)
+            let force_magnitude = -bond.k * stretch;
+            let force = r * (force_magnitude / distance);
+            forces[i] += force;
+            forces[j] -= force;
+            energy += 0.5 * bond.k * stretch * stretch;
+            virial += force_magnitude * distance;
+        }
+
+        for angle in &self.angles {
+            let (i, j, k) = angle.particles;
+            let (r_ij, r_kj) = (vector(i, j), vector(k, j));
+            let (d_ij, d_kj) = (r_ij.norm(), r_kj.norm());
+            let cos = (r_ij.dot(r_kj) / (d_ij * d_kj)).clamp(-1.0, 1.0);
+            let theta = cos.acos();
+            let deviation = theta - angle.theta0;
+            // dV/dcos(θ) = dV/dθ * dθ/dcos(θ) = k * (θ - θ_0) * -1 / sin(θ)
+            let sin = (1.0 - cos * cos).sqrt().max(f64::EPSILON);
+            let dv_dcos = -angle.k * deviation / sin;
+            // F_i = -dV/dcos(θ) * dcos(θ)/dr_i, and likewise for k.
+            let force_i = (r_kj / (d_ij * d_kj) - r_ij * (cos / (d_ij * d_ij))) * -dv_dcos;
+            let force_k = (r_ij / (d_ij * d_kj) - r_kj * (cos / (d_kj * d_kj))) * -dv_dcos;
+            forces[i] += force_i;
+            forces[j] -= force_i + force_k;
+            forces[k] += force_k;
+            energy += 0.5 * angle.k * deviation * deviation;
+            virial += r_ij.dot(force_i) + r_kj.dot(force_k);
+        }
+
+        for dihedral in &self.dihedrals {
+            let (i, j, k, l) = dihedral.particles;
+            let geometry = DihedralGeometry::new(vector(i, j), vector(k, j), vector(k, l));
+            let (dihedral_energy, dv_dphi) = dihedral.potential.energy(geometry.phi);
+            energy += dihedral_energy;
+            virial += geometry.apply(dv_dphi, (i, j, k, l), forces);
+        }
+
+        for improper in &self.impropers {
+            let (i, j, k, l) = improper.particles;
+            let geometry = DihedralGeometry::new(vector(i, j), vector(k, j), vector(k, l));
+            // Take the deviation between -π and π, such that it is the smallest rotation.
+            let deviation = (geometry.phi - improper.xi0 + PI).rem_euclid(2.0 * PI) - PI;
+            energy += 0.5 * improper.k * deviation * deviation;
+            virial += geometry.apply(improper.k * deviation, (i, j, k, l), forces);
+        }
+
+        (energy, virial)
+    }
+}
+
+/// The vectors between four particles i, j, k, l, and the dihedral angle between the planes
+/// through i, j, k and j, k, l.
+struct DihedralGeometry {
+    r_ij: Vec3,
+    r_kj: Vec3,
+    r_kl: Vec3,
+    /// Normal of the plane through i, j, k.
+    m: Vec3,
+    /// Normal of the plane through j, k, l.
+    n: Vec3,
+    /// Dihedral angle (rad).
+    phi: f64,
+}
+
+/// Squared sine of the angle between two bonds of a dihedral below which its three particles are
+/// taken to be on a line.
+const MIN_SIN2: f64 = 1e-12;
+
+impl DihedralGeometry {
+    /// Creates the geometry of a dihedral from the vectors r_i - r_j, r_k - r_j and r_k - r_l.
+    fn new(r_ij: Vec3, r_kj: Vec3, r_kl: Vec3) -> Self {
+        let m = r_ij.cross(r_kj);
+        let n = r_kj.cross(r_kl);
+        // The sign of the angle follows from which side of the plane of j, k, l particle i is on.
+        let magnitude = m.cross(n).norm().atan2(m.dot(n));
+        let phi = if r_ij.dot(n) < 0.0 {
+            -magnitude
+        } else {
+            magnitude
+        };
+        Self {
+            r_ij,
+            r_kj,
+            r_kl,
+            m,
+            n,
+            phi,
+        }
+    }
+
+    /// Adds the forces on the particles due to a derivative of the energy to the dihedral angle
+    /// `dv_dphi` (J/rad), and returns their virial (J).
+    ///
+    /// See Bekker, H. (1996). Unification of Box Shapes in Molecular Simulations. J. Comput. Chem.
+    fn apply(
+        &self,
+        dv_dphi: f64,
+        (i, j, k, l): (usize, usize, usize, usize),
+        forces: &mut [Vec3],
+    ) -> f64 {
+        let d_kj2 = self.r_kj.dot(self.r_kj);
+        let d_kj = d_kj2.sqrt();
+        let (m2, n2) = (self.m.dot(self.m), self.n.dot(self.n));
+        // With three of the particles on a line, the dihedral angle is undefined, and so is its
+        // force.
+        if m2 <= MIN_SIN2 * self.r_ij.dot(self.r_ij) * d_kj2
+            || n2 <= MIN_SIN2 * self.r_kl.dot(self.r_kl) * d_kj2
+        {
+            return 0.0;
+        }
+        let force_i = self.m * (-dv_dphi * d_kj / m2);
+        let force_l = self.n * (dv_dphi * d_kj / n2);
+        let p = self.r_ij.dot(self.r_kj) / d_kj2;
+        let q = self.r_kl.dot(self.r_kj) / d_kj2;
+        let s = force_i * p - force_l * q;
+        let force_j = s - force_i;
+        let force_k = -s - force_l;
+        forces[i] += force_i;
+        forces[j] += force_j;
+        forces[k] += force_k;
+        forces[l] += force_l;
+        // Relative to particle j, particle l is at r_kj - r_kl.
+        self.r_ij.dot(force_i) + self.r_kj.dot(force_k) + (self.r_kj - self.r_kl).dot(force_l)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    /// Returns a chain of four particles with bonds of roughly 0.15 nm at random angles.
+    fn chain(seed: u64) -> Vec<Particle> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut pos = Vec3::zero();
+        let mut particles = Vec::new();
+        for _ in 0..4 {
+            particles.push(Particle::new(pos, Vec3::zero(), Vec3::zero(), 1e-26));
+            let step = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            );
+            pos += step * (0.15e-9 / step.norm());
+        }
+        particles
+    }
+
+    /// Checks the forces of a topology against the central differences of its energy, and its
+    /// virial against the sum of r ⋅ F.
+    fn check_forces(topology: &Topology) {
+        for seed in 0..20 {
+            check_forces_at(topology, &chain(seed));
+        }
+    }
+
+    /// Checks the forces of a topology on some particles, as [`check_forces`].
+    fn check_forces_at(topology: &Topology, particles: &[Particle]) {
+        let boundary = Vec3::one() * 10e-9;
+        let mut forces = vec![Vec3::zero(); particles.len()];
+        let (_, virial) = topology.forces(particles, boundary, &mut forces);
+        let scale = forces.iter().map(|f| f.norm()).fold(0.0, f64::max);
+
+        let energy = |particles: &[Particle]| {
+            let mut forces = vec![Vec3::zero(); particles.len()];
+            topology.forces(particles, boundary, &mut forces).0
+        };
+        let h = 1e-16;
+        for i in 0..particles.len() {
+            let mut gradient = [0.0; 3];
+            for (axis, component) in gradient.iter_mut().enumerate() {
+                let displaced = |delta: f64| {
+                    let mut particles = particles.to_vec();
+                    let pos = &mut particles[i].pos;
+                    match axis {
+                        0 => pos.x += delta,
+                        1 => pos.y += delta,
+                        _ => pos.z += delta,
+                    }
+                    energy(&particles)
+                };
+                *component = (displaced(h) - displaced(-h)) / (2.0 * h);
+            }
+            let numerical = -Vec3::new(gradient[0], gradient[1], gradient[2]);
+            let error = (forces[i] - numerical).norm();
+            assert!(
+                error < 1e-5 * scale,
+                "particle {i}: analytic {:?}, numerical {numerical:?}",
+                forces[i]
+            );
+        }
+
+        // The chain does not cross the boundary, so the positions can be used directly.
+        let expected: f64 = particles
+            .iter()
+            .zip(&forces)
+            .map(|(p, f)| p.pos.dot(*f))
+            .sum();
+        assert!((virial - expected).abs() < 1e-9 * scale * 0.15e-9);
+    }
+
+    #[test]
+    fn bond_forces_match_finite_differences() {
+        let mut topology = Topology::new();
+        topology.add_bond(Bond {
+            particles: (1, 2),
+            k: 0.5,
+            r0: 0.1e-9,
+        });
+        check_forces(&topology);
+    }
+
+    #[test]
+    fn angle_forces_match_finite_differences() {
+        let mut topology = Topology::new();
+        topology.angles.push(Angle {
+            particles: (0, 1, 2),
+            k: 1e-19,
+            theta0: 1.9,
+        });
+        check_forces(&topology);
+    }
+
+    #[test]
+    fn dihedral_forces_match_finite_differences() {
+        let mut topology = Topology::new();
+        topology.dihedrals.push(Dihedral {
+            particles: (0, 1, 2, 3),
+            potential: DihedralPotential::Periodic {
+                k: 1e-20,
+                phi0: 0.3,
+                n: 3,
+            },
+        });
+        check_forces(&topology);
+
+        let mut topology = Topology::new();
+        topology.dihedrals.push(Dihedral {
+            particles: (0, 1, 2, 3),
+            potential: DihedralPotential::RyckaertBellemans {
+                coefficients: [1.5e-20, 1.9e-20, -0.5e-20, -2.9e-20, 0.3e-20, -0.1e-20],
+            },
+        });
+        check_forces(&topology);
+    }
+
+    #[test]
+    fn dihedral_forces_stay_finite_on_a_line() {
+        let mut topology = Topology::new();
+        topology.dihedrals.push(Dihedral {
+            particles: (0, 1, 2, 3),
+            potential: DihedralPotential::Periodic {
+                k: 1e-20,
+                phi0: 0.3,
+                n: 3,
+            },
+        });
+        let at = |x: f64, y: f64, z: f64| {
+            Particle::new(Vec3::new(x, y, z) * 1e-9, Vec3::zero(), Vec3::zero(), 1e-26)
+        };
+        // Nearly on a line, the forces are large, but still follow from the energy.
+        let bent = [
+            at(-0.15, 1e-4, 0.0),
+            at(0.0, 0.0, 0.0),
+            at(0.15, 0.0, 0.0),
+            at(0.2, 0.1, 0.05),
+        ];
+        check_forces_at(&topology, &bent);
+
+        // On a line, there are no forces at all, rather than NaN.
+        let straight = [
+            at(-0.15, 0.0, 0.0),
+            at(0.0, 0.0, 0.0),
+            at(0.15, 0.0, 0.0),
+            at(0.2, 0.1, 0.05),
+        ];
+        let mut forces = vec![Vec3::zero(); 4];
+        let (energy, virial) = topology.forces(&straight, Vec3::one() * 10e-9, &mut forces);
+        assert!(energy.is_finite() && virial == 0.0);
+        assert!(forces.iter().all(|f| *f == Vec3::zero()), "{forces:?}");
+    }
+
+    #[test]
+    fn improper_forces_match_finite_differences() {
+        let mut topology = Topology::new();
+        topology.impropers.push(Improper {
+            particles: (0, 1, 2, 3),
+            k: 1e-19,
+            xi0: 0.2,
+        });
+        check_forces(&topology);
+    }
+
+    #[test]
+    fn exclusions_follow_the_bond_graph() {
+        // A chain of five particles, the last three of which also form a triangle.
+        let bond = |i, j| Bond {
+            particles: (i, j),
+            k: 1.0,
+            r0: 1.0,
+        };
+        let molecule = Molecule {
+            name: "RING".to_string(),
+            species: vec![0; 5],
+            bonds: vec![bond(0, 1), bond(1, 2), bond(2, 3), bond(3, 4), bond(4, 2)],
+            angles: Vec::new(),
+            dihedrals: Vec::new(),
+            impropers: Vec::new(),
+            constraints: Vec::new(),
+            settles: Vec::new(),
+        };
+        let mut topology = Topology::new();
+        topology.add_molecule(&molecule, 10);
+
+        let excluded: Vec<_> = topology.exclusions().collect();
+        #[rustfmt::skip]
+        let expected = [
+            (10, 11), (10, 12), (10, 13), (10, 14),
+            (11, 12), (11, 13), (11, 14),
+            (12, 13), (12, 14),
+            (13, 14),
+        ];
+        assert_eq!(excluded, expected);
+        // Within the triangle, every pair is at most two bonds apart.
+        assert_eq!(topology.pairs, [(10, 13), (10, 14)]);
+    }
+}
@@ -26,10 +26,20 @@ impl Trajectory {
         }
     }
 
+    /// Add a frame of the current state of the universe, with the velocities at the time of the
+    /// positions.
     pub fn add_frame_from_universe(&mut self, u: &Universe) {
+        let particles = u
+            .particles
+            .iter()
+            .map(|particle| Particle {
+                vel: u.synchronized_velocity(particle),
+                ..*particle
+            })
+            .collect();
         self.frames.push(Frame {
             time: u.time,
-            particles: u.particles.clone(),
+            particles,
         })
     }
 
@@ -1,19 +1,29 @@
+use crate::constraints::{
+    self, Constraint, ConstraintError, ConstraintSolver, Settle, DEFAULT_CONSTRAINT_TOLERANCE,
+    DEFAULT_MAX_ITERATIONS,
+};
+use crate::electrostatics::Electrostatics;
+use crate::integrator::{Integrator, VelocityVerlet};
+use crate::neighbours::VerletList;
+use crate::potential::{LennardJones, PairTable};
+use crate::thermostat::NoseHooverChain;
 use crate::time::Time;
+use crate::topology::Topology;
 use crate::vec3::Vec3;
 
-const BOLTZMANN: f64 = 1.380649e-23; // J⋅K−1
-#[rustfmt::skip]
-const NEIGHBOURS: [(isize, isize, isize); 9 * 3] = [
-    (-1, -1, -1), (-1, -1,  0), (-1, -1,  1), 
-    (-1,  0, -1), (-1,  0,  0), (-1,  0,  1), 
-    (-1,  1, -1), (-1,  1,  0), (-1,  1,  1), 
-    ( 0, -1, -1), ( 0, -1,  0), ( 0, -1,  1), 
-    ( 0,  0, -1), ( 0,  0,  0), ( 0,  0,  1), 
-    ( 0,  1, -1), ( 0,  1,  0), ( 0,  1,  1), 
-    ( 1, -1, -1), ( 1, -1,  0), ( 1, -1,  1), 
-    ( 1,  0, -1), ( 1,  0,  0), ( 1,  0,  1), 
-    ( 1,  1, -1), ( 1,  1,  0), ( 1,  1,  1), 
-];
+pub(crate) const BOLTZMANN: f64 = 1.380649e-23; // J⋅K−1
+/// Default interaction cutoff (meter).
+pub(crate) const DEFAULT_CUTOFF: f64 = 1.0e-9;
+/// Default depth of the Lennard-Jones potential well (J/mol).
+pub(crate) const DEFAULT_EPSILON: f64 = 1.8e4;
+/// Default distance at which the Lennard-Jones potential crosses zero (meter).
+pub(crate) const DEFAULT_SIGMA: f64 = 2.0e-10;
+/// Default mass of a particle (kg).
+pub(crate) const DEFAULT_MASS: f64 = 1e-24;
+/// Default neighbour list skin (meter).
+pub(crate) const DEFAULT_SKIN: f64 = 0.1e-9;
+/// Default time constant of the coupling to the temperature (seconds).
+pub(crate) const DEFAULT_COUPLING_TIME: f64 = 0.1e-12;
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Particle {
@@ -22,9 +32,13 @@ pub struct Particle {
     /// Velocity in meters / second.
     pub(crate) vel: Vec3,
     /// Acceleration in meters / second^2.
-    acc: Vec3,
+    pub(crate) acc: Vec3,
     /// Mass in kg.
-    mass: f64,
+    pub(crate) mass: f64,
+    /// Index of the species of the particle.
+    pub(crate) species: usize,
+    /// Charge in Coulomb.
+    pub(crate) charge: f64,
 }
 
 impl Particle {
@@ -34,18 +48,82 @@ impl Particle {
             vel,
             acc,
             mass,
+            species: 0,
+            charge: 0.0,
         }
     }
+
+    /// Set the species.
+    pub const fn species(mut self, species: usize) -> Self {
+        self.species = species;
+        self
+    }
+
+    /// Set the charge.
+    pub const fn charge(mut self, charge: f64) -> Self {
+        self.charge = charge;
+        self
+    }
 }
 
-#[derive(Debug, Clone)]
+/// Temperature control.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Thermostat {
+    /// No temperature control. The energy of the system is conserved (NVE).
+    None,
+    /// Weak coupling of Berendsen, which scales the velocities such that the temperature relaxes
+    /// to the set point exponentially, with a time constant τ (seconds).
+    ///
+    /// ```
+    /// λ = sqrt(1 + Δt / τ * (T0 / T - 1))
+    /// ```
+    ///
+    /// See Berendsen, H. J. C., Postma, J. P. M., van Gunsteren, W. F., DiNola, A. & Haak, J. R.
+    /// (1984). J. Chem. Phys. 81, 3684.
+    Berendsen { tau: f64 },
+    /// A chain of Nosé-Hoover thermostats, with the period of the oscillations of the temperature
+    /// (seconds) and the number of thermostats. It samples the canonical ensemble, and conserves
+    /// the energy of the system together with that of the thermostats.
+    ///
+    /// See [`NoseHooverChain`].
+    NoseHoover { period: f64, chain: usize },
+}
+
+#[derive(Debug)]
 pub struct Universe {
     pub time: Time,
     pub(crate) iteration: usize,
     pub(crate) dt: Time,
     pub(crate) boundary: Vec3,
+    /// Interaction cutoff in meters.
+    pub(crate) cutoff: f64,
+    pub(crate) neighbour_list: VerletList,
+    /// Interactions between every pair of species, for particles within the cutoff.
+    pub(crate) potentials: PairTable,
+    /// Treatment of the interactions between charges.
+    pub(crate) electrostatics: Electrostatics,
+    /// Bonds between the particles.
+    pub(crate) topology: Topology,
+    /// Scaling factor of the Lennard-Jones interactions between 1-4 pairs.
+    pub(crate) fudge_lj: f64,
+    /// Scaling factor of the Coulomb interactions between 1-4 pairs.
+    pub(crate) fudge_coulomb: f64,
+    /// Algorithm to satisfy the constraints in the topology.
+    pub(crate) constraint_solver: ConstraintSolver,
+    /// Scheme to advance the particles over a time step.
+    pub(crate) integrator: Box<dyn Integrator>,
+    /// Potential energy in Joules, as of the last evaluation of the forces.
+    pub(crate) potential_energy: f64,
+    /// Virial (sum of r_ij ⋅ F_ij over all pairs) in Joules, as of the last evaluation of the
+    /// forces.
+    pub(crate) virial: f64,
+    /// Whether to correct the energy and pressure for the interactions beyond the cutoff.
+    pub(crate) dispersion_correction: bool,
     /// Temperature in Kelvin.
     pub(crate) temperature: f64,
+    pub(crate) thermostat: Thermostat,
+    /// State of the Nosé-Hoover thermostats, if any.
+    pub(crate) chain: NoseHooverChain,
     pub(crate) particles: Vec<Particle>,
 }
 
@@ -57,7 +135,23 @@ impl Universe {
             iteration: 0,
             dt: timestep,
             boundary,
+            cutoff: DEFAULT_CUTOFF,
+            neighbour_list: VerletList::new(DEFAULT_SKIN),
+            potentials: PairTable::single(LennardJones::from_molar(DEFAULT_EPSILON, DEFAULT_SIGMA)),
+            electrostatics: Electrostatics::None,
+            topology: Topology::new(),
+            fudge_lj: 1.0,
+            fudge_coulomb: 1.0,
+            constraint_solver: ConstraintSolver::default(),
+            integrator: Box::new(VelocityVerlet),
+            potential_energy: 0.0,
+            virial: 0.0,
+            dispersion_correction: false,
             temperature,
+            thermostat: Thermostat::Berendsen {
+                tau: DEFAULT_COUPLING_TIME,
+            },
+            chain: NoseHooverChain::default(),
             particles: Vec::new(),
         }
     }
@@ -74,6 +168,81 @@ impl Universe {
         self
     }
 
+    /// Set the interaction cutoff.
+    ///
+    /// The cutoff must not be larger than half the smallest edge of the boundary, which
+    /// [`Universe::prepare`] checks.
+    pub fn cutoff(mut self, cutoff: f64) -> Self {
+        self.cutoff = cutoff;
+        self
+    }
+
+    /// Set the neighbour list skin.
+    pub fn skin(mut self, skin: f64) -> Self {
+        self.neighbour_list = self.neighbour_list.skin(skin);
+        self
+    }
+
+    /// Set the maximum number of steps between neighbour list rebuilds.
+    pub fn rebuild_interval(mut self, interval: Option<usize>) -> Self {
+        self.neighbour_list = self.neighbour_list.interval(interval);
+        self
+    }
+
+    /// Set the interactions between every pair of species.
+    pub fn potentials(mut self, potentials: PairTable) -> Self {
+        self.potentials = potentials;
+        self
+    }
+
+    /// Set the treatment of the interactions between charges.
+    pub fn electrostatics(mut self, electrostatics: Electrostatics) -> Self {
+        self.electrostatics = electrostatics;
+        self
+    }
+
+    /// Set the bonds between the particles.
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Set the scaling factors of the Lennard-Jones and Coulomb interactions between particles
+    /// that are three bonds apart (1-4 pairs).
+    pub fn fudge_factors(mut self, lj: f64, coulomb: f64) -> Self {
+        self.fudge_lj = lj;
+        self.fudge_coulomb = coulomb;
+        self
+    }
+
+    /// Set the algorithm to satisfy the constraints.
+    pub fn constraint_solver(mut self, constraint_solver: ConstraintSolver) -> Self {
+        self.constraint_solver = constraint_solver;
+        self
+    }
+
+    /// Set whether to correct the energy and pressure for the interactions beyond the cutoff.
+    pub fn dispersion_correction(mut self, dispersion_correction: bool) -> Self {
+        self.dispersion_correction = dispersion_correction;
+        self
+    }
+
+    /// Set the integrator.
+    pub fn integrator(mut self, integrator: Box<dyn Integrator>) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    /// Set the thermostat.
+    pub fn thermostat(mut self, thermostat: Thermostat) -> Self {
+        self.thermostat = thermostat;
+        self.chain = match thermostat {
+            Thermostat::NoseHoover { chain, .. } => NoseHooverChain::new(chain),
+            _ => NoseHooverChain::default(),
+        };
+        self
+    }
+
     /// Add a [`Particle`] to the system.
     pub fn add_particle(mut self, particle: Particle) -> Self {
         self.particles.push(particle);
@@ -87,71 +256,431 @@ impl Universe {
     }
 }
 
-/// Interatomic potential according to
-/// [Lennard-Jones potential](https://en.wikipedia.org/wiki/Lennard-Jones_potential).
-///
-/// ```
-/// V_LJ(r) = 4 * ε * [ ( σ / r ) ^ 12 − ( σ / r ) ^ 6 ]
-/// ```
-///
-///  - ε is the depth of the potential well. (J/mol)
-///  - σ is the distance at which the potential crosses zero. (meter)
-#[inline]
-pub fn lennard_jones_potential(r: Vec3) -> Vec3 {
-    const EPSILON: f64 = 1.8e4; // J/mol
-    const SIGMA: f64 = 2.0e-10; // m
+/// Failure to prepare the start configuration of a [`Universe`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrepareError {
+    /// The cutoff (meter) is larger than half the smallest edge of the boundary, such that a
+    /// particle could interact with more than one image of another.
+    CutoffTooLarge { cutoff: f64, smallest_edge: f64 },
+    /// The particles could not be moved onto their constraints.
+    Constraint(ConstraintError),
+}
+
+impl std::fmt::Display for PrepareError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrepareError::CutoffTooLarge {
+                cutoff,
+                smallest_edge,
+            } => write!(
+                f,
+                "the cutoff of {cutoff:e} m is larger than half the smallest edge of the \
+                 boundary, {smallest_edge:e} m"
+            ),
+            PrepareError::Constraint(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PrepareError {}
 
-    let sigma_over_r = SIGMA / r.norm();
-    let frac_pow_6 = sigma_over_r.powi(6);
+impl From<ConstraintError> for PrepareError {
+    fn from(error: ConstraintError) -> Self {
+        PrepareError::Constraint(error)
+    }
+}
 
-    r * ((frac_pow_6 * frac_pow_6 - frac_pow_6) * 4.0 * EPSILON)
+/// Returns the shortest vector between two particles that are separated by `r` in some periodic
+/// boundary, according to the minimum-image convention.
+///
+/// This is only the correct separation for interactions if the cutoff is at most half the
+/// smallest edge of the boundary.
+pub fn minimum_image(r: Vec3, boundary: Vec3) -> Vec3 {
+    let Vec3 { x, y, z } = r / boundary;
+    r - boundary * Vec3::new(x.round(), y.round(), z.round())
 }
 
 impl Universe {
-    /// Apply one time step.
-    pub fn step(&mut self) {
-        // Predictor stage.
+    /// Prepare the start configuration: move the particles onto their constraints, remove the
+    /// velocities along them, and compute the forces that the first step starts from.
+    ///
+    /// The start configuration need not satisfy the constraints, but the cutoff must not be larger
+    /// than half the smallest edge of the boundary.
+    pub fn prepare(&mut self) -> Result<(), PrepareError> {
+        let smallest_edge = self.boundary.x.min(self.boundary.y).min(self.boundary.z);
+        if self.cutoff > 0.5 * smallest_edge {
+            return Err(PrepareError::CutoffTooLarge {
+                cutoff: self.cutoff,
+                smallest_edge,
+            });
+        }
+        let settles = self.topology.settles.iter().flat_map(Settle::constraints);
+        let all: Vec<Constraint> = self
+            .topology
+            .constraints
+            .iter()
+            .copied()
+            .chain(settles)
+            .collect();
+        constraints::relax(
+            &all,
+            &mut self.particles,
+            self.boundary,
+            DEFAULT_CONSTRAINT_TOLERANCE,
+            DEFAULT_MAX_ITERATIONS,
+        )?;
+        self.constrain_velocities()?;
+        self.remove_centre_of_mass_velocity();
+        self.apply_boundary_conditions();
+        let forces = self.compute_forces();
+        for (particle, force) in self.particles.iter_mut().zip(forces) {
+            particle.acc = force / particle.mass;
+        }
+        Ok(())
+    }
+
+    /// Remove the velocity of the centre of mass, which is not part of the degrees of freedom.
+    fn remove_centre_of_mass_velocity(&mut self) {
+        let mass: f64 = self.particles.iter().map(|particle| particle.mass).sum();
+        if mass <= 0.0 {
+            return;
+        }
+        let momentum = (self.particles.iter())
+            .fold(Vec3::zero(), |p, particle| p + particle.vel * particle.mass);
+        let velocity = momentum / mass;
         for particle in &mut self.particles {
-            // Move the particles. pos = pos + vel * Δt + 1/2 * acc * Δt^2
-            particle.pos += particle.vel * self.dt + particle.acc * self.dt * self.dt * 0.5;
-            // Update velocities. vel = vel + acc * Δt
-            particle.vel += particle.acc * self.dt;
+            particle.vel -= velocity;
         }
+    }
+
+    /// Apply one time step.
+    ///
+    /// Returns an error if the constraints cannot be satisfied.
+    pub fn step(&mut self) -> Result<(), ConstraintError> {
+        let reference: Vec<Vec3> = self.particles.iter().map(|p| p.pos).collect();
+
+        // The Nosé-Hoover thermostats act on the velocities over half a step before, and half a
+        // step after the particles are moved.
+        let dt = self.dt.seconds();
+        self.apply_nose_hoover(0.5 * dt);
+
+        // Move the particles.
+        self.integrator.update_positions(&mut self.particles, dt);
+
+        // Move the particles back onto their constraints (SHAKE or LINCS, and SETTLE).
+        let mut constraint_virial = self.constrain_positions(&reference)?;
+
+        // Apply boundary conditions.
+        self.apply_boundary_conditions();
 
         // Get forces and adjust accelerations.
-        // TODO: Huge optimization possible, here, since F_ij = -F_ji.
-        let other_positions: Vec<_> = self.particles.iter().map(|p| p.pos).collect();
-        for (index, particle) in self.particles.iter_mut().enumerate() {
-            let mut force = Vec3::zero();
-            // dx, dy, dz are the offset factors for the surrounding pbc boxes.
-            for (x, y, z) in NEIGHBOURS {
-                let adjustment = Vec3::new(x as f64, y as f64, z as f64) * self.boundary;
-                // Get forces.
-                // F = - ∇V(pos)
-                //
-                // We can obtain this force by simply negating the Lennard-Jones potential. With the
-                // small timestep (dt) we integrate this so we can treat it as a force in our model.
-                for (other_index, other_pos) in other_positions.iter().enumerate() {
-                    if (x, y, z) == (0, 0, 0) && index == other_index {
-                        continue;
-                    }
-                    let other_pos_adjusted = adjustment + *other_pos;
-                    let r = particle.pos - other_pos_adjusted;
-                    force -= lennard_jones_potential(r);
-                }
-            }
+        let forces = self.compute_forces();
+        for (particle, force) in self.particles.iter_mut().zip(forces) {
             // Update acceleration. a = F / m
             particle.acc = force / particle.mass;
         }
+        self.integrator.update_velocities(&mut self.particles, dt);
 
-        // // Corrector stage.
-        // for particle in &mut self.particles {
-        //     // Adjust predicted particle positions and velocities based on new acceleration.
-        //     particle.pos += adjust(particle.acc, self.dt);
-        //     particle.vel += adjust(particle.acc, self.dt);
-        // }
+        // Remove the velocities along the constraints (RATTLE). Velocities half a step behind the
+        // positions already follow from the constrained positions.
+        if !self.integrator.half_step_velocities() {
+            constraint_virial += self.constrain_velocities()?;
+        }
+        // With synchronous velocities, both stages estimate the constraint forces over the whole
+        // step, at its start and at its end, so their virials are averaged. With half-step
+        // velocities, the constraint forces move the particles twice as far as the position
+        // stage assumes (m * Δr = F * Δt^2), which halves its virial all the same.
+        self.virial += 0.5 * constraint_virial;
 
-        // Apply boundary conditions.
+        // Apply temperature control.
+        self.apply_nose_hoover(0.5 * dt);
+        if let Thermostat::Berendsen { tau } = self.thermostat {
+            let current = self.kinetic_temperature();
+            let squared = 1.0 + dt / tau * (self.temperature / current - 1.0);
+            // Limit the scaling to between 0.8 and 1.25 as GROMACS does, for a start far from the
+            // temperature.
+            let lambda = squared.clamp(0.8 * 0.8, 1.25 * 1.25).sqrt();
+            for particle in &mut self.particles {
+                particle.vel = particle.vel * lambda;
+            }
+        }
+
+        // Apply pressure control.
+        // TODO: Implement pressure control.
+
+        // Increase time and iteration count.
+        self.time += self.dt;
+        self.iteration += 1;
+
+        Ok(())
+    }
+
+    /// Propagate the Nosé-Hoover thermostats over a time `dt` (seconds), and scale the velocities
+    /// of the particles accordingly. Scaling all velocities keeps them along the constraints.
+    fn apply_nose_hoover(&mut self, dt: f64) {
+        if let Thermostat::NoseHoover { period, .. } = self.thermostat {
+            let kinetic: f64 = (self.particles.iter())
+                .map(|p| 0.5 * p.mass * p.vel.norm().powi(2))
+                .sum();
+            let dof = self.degrees_of_freedom();
+            let scale = self
+                .chain
+                .propagate(kinetic, dof, self.temperature, period, dt);
+            for particle in &mut self.particles {
+                particle.vel = particle.vel * scale;
+            }
+        }
+    }
+
+    /// Move the particles onto their constraints, from the reference positions at the start of the
+    /// step. Returns the virial of the constraint forces.
+    pub(crate) fn constrain_positions(
+        &mut self,
+        reference: &[Vec3],
+    ) -> Result<f64, ConstraintError> {
+        let dt = self.dt.seconds();
+        let virial = self.constraint_solver.positions(
+            &self.topology.constraints,
+            reference,
+            &mut self.particles,
+            self.boundary,
+            dt,
+        )?;
+        let settle_virial = constraints::settle(
+            &self.topology.settles,
+            reference,
+            &mut self.particles,
+            self.boundary,
+            dt,
+        )?;
+        Ok(virial + settle_virial)
+    }
+
+    /// Remove the velocities along the constraints. Returns the virial of the constraint forces.
+    pub(crate) fn constrain_velocities(&mut self) -> Result<f64, ConstraintError> {
+        let dt = self.dt.seconds();
+        let virial = self.constraint_solver.velocities(
+            &self.topology.constraints,
+            &mut self.particles,
+            self.boundary,
+            dt,
+        )?;
+        let settle_virial = constraints::settle_velocities(
+            &self.topology.settles,
+            &mut self.particles,
+            self.boundary,
+            dt,
+        )?;
+        Ok(virial + settle_virial)
+    }
+
+    /// Compute the force on every particle, and store the potential energy of the system.
+    pub(crate) fn compute_forces(&mut self) -> Vec<Vec3> {
+        // Rebuild the neighbour list if any particle may have moved into the cutoff of a particle
+        // that is not in its list.
+        if self.neighbour_list.is_stale(self.boundary, &self.particles) {
+            let topology = &self.topology;
+            self.neighbour_list
+                .build(self.boundary, self.cutoff, &self.particles, |i, j| {
+                    topology.excludes(i, j)
+                });
+        }
+        self.neighbour_list.tick();
+
+        // Every pair is visited once. Since F_ij = -F_ji, the force on one particle of the pair
+        // is the negation of the force on the other.
+        let mut forces = vec![Vec3::zero(); self.particles.len()];
+        let mut energy = 0.0;
+        let mut virial = 0.0;
+        for (index, other_index) in self.neighbour_list.pairs() {
+            let (particle, other) = (&self.particles[index], &self.particles[other_index]);
+            // Only the closest periodic image of the other particle can be within the cutoff.
+            let r = minimum_image(particle.pos - other.pos, self.boundary);
+            let distance = r.norm();
+            if distance < self.cutoff {
+                let potential = self.potentials.get(particle.species, other.species);
+                let (coulomb_energy, coulomb_force) = self
+                    .electrostatics
+                    .pair(particle.charge * other.charge, distance);
+                let force_magnitude = potential.force(distance) + coulomb_force;
+                // F = - ∇V(r), which points along r.
+                let force = r * (force_magnitude / distance);
+                forces[index] += force;
+                forces[other_index] -= force;
+                energy += potential.energy(distance) + coulomb_energy;
+                virial += force_magnitude * distance;
+            }
+        }
+        // The excluded pairs are not in the neighbour list, but may still need a correction.
+        for (index, other_index) in self.topology.exclusions() {
+            let (particle, other) = (&self.particles[index], &self.particles[other_index]);
+            let r = minimum_image(particle.pos - other.pos, self.boundary);
+            let distance = r.norm();
+            let (correction_energy, force_magnitude) = self
+                .electrostatics
+                .excluded(particle.charge * other.charge, distance);
+            let force = r * (force_magnitude / distance);
+            forces[index] += force;
+            forces[other_index] -= force;
+            energy += correction_energy;
+            virial += force_magnitude * distance;
+        }
+        // The 1-4 pairs interact through scaled Lennard-Jones and plain Coulomb interactions, which
+        // are bonded-like, and so neither cut off nor modified at the cutoff.
+        for &(index, other_index) in &self.topology.pairs {
+            let (particle, other) = (&self.particles[index], &self.particles[other_index]);
+            let r = minimum_image(particle.pos - other.pos, self.boundary);
+            let distance = r.norm();
+            let potential = self
+                .potentials
+                .get_unmodified(particle.species, other.species);
+            let (coulomb_energy, coulomb_force) = self
+                .electrostatics
+                .coulomb(particle.charge * other.charge, distance);
+            let force_magnitude =
+                self.fudge_lj * potential.force(distance) + self.fudge_coulomb * coulomb_force;
+            let force = r * (force_magnitude / distance);
+            forces[index] += force;
+            forces[other_index] -= force;
+            energy +=
+                self.fudge_lj * potential.energy(distance) + self.fudge_coulomb * coulomb_energy;
+            virial += force_magnitude * distance;
+        }
+        let (bonded_energy, bonded_virial) =
+            self.topology
+                .forces(&self.particles, self.boundary, &mut forces);
+        let (long_range_energy, long_range_virial) =
+            self.electrostatics
+                .long_range(&self.particles, self.boundary, &mut forces);
+        self.potential_energy = energy + bonded_energy + long_range_energy;
+        self.virial = virial + bonded_virial + long_range_virial;
+
+        forces
+    }
+
+    /// Returns the potential energy in Joules, as of the last evaluation of the forces.
+    pub fn potential_energy(&self) -> f64 {
+        if self.dispersion_correction {
+            self.potential_energy + self.tail_correction().0
+        } else {
+            self.potential_energy
+        }
+    }
+
+    /// Returns the pressure in Pascal, as of the last evaluation of the forces.
+    ///
+    /// ```
+    /// P = (2 * E_kin + W) / (3 * V)
+    /// ```
+    ///
+    /// where W is the virial.
+    pub fn pressure(&self) -> f64 {
+        let volume = self.volume();
+        let pressure = (2.0 * self.kinetic_energy() + self.virial) / (3.0 * volume);
+        if self.dispersion_correction {
+            pressure + self.tail_correction().1
+        } else {
+            pressure
+        }
+    }
+
+    /// Returns the volume of the boundary in cubic meters.
+    pub fn volume(&self) -> f64 {
+        self.boundary.x * self.boundary.y * self.boundary.z
+    }
+
+    /// Returns the corrections to the energy (J) and pressure (Pa) for the interactions beyond
+    /// the cutoff, assuming that particles are distributed uniformly there.
+    ///
+    /// ```
+    /// E_tail = 1/2 * Σ_a Σ_b N_a * ρ_b * ∫_{r_c}^∞ V_ab(r) * 4 * π * r^2 dr
+    /// P_tail = 1/6 * Σ_a Σ_b ρ_a * ρ_b * ∫_{r_c}^∞ r * F_ab(r) * 4 * π * r^2 dr
+    /// ```
+    fn tail_correction(&self) -> (f64, f64) {
+        let volume = self.volume();
+        let n_species = self.potentials.species();
+        let mut counts = vec![0usize; n_species];
+        for particle in &self.particles {
+            counts[particle.species] += 1;
+        }
+        let mut energy = 0.0;
+        let mut pressure = 0.0;
+        for a in 0..n_species {
+            for b in 0..n_species {
+                let potential = self.potentials.get(a, b);
+                let density_a = counts[a] as f64 / volume;
+                let density_b = counts[b] as f64 / volume;
+                energy += 0.5 * counts[a] as f64 * density_b * potential.tail_energy(self.cutoff);
+                pressure += density_a * density_b * potential.tail_virial(self.cutoff) / 6.0;
+            }
+        }
+        (energy, pressure)
+    }
+
+    /// Returns the velocity of a particle at the time of its position.
+    ///
+    /// Velocities that are kept half a step behind the positions are brought forward with the
+    /// current acceleration.
+    ///
+    /// ```
+    /// v(t) = v(t - Δt/2) + 1/2 * a(t) * Δt
+    /// ```
+    pub fn synchronized_velocity(&self, particle: &Particle) -> Vec3 {
+        if self.integrator.half_step_velocities() {
+            particle.vel + particle.acc * (0.5 * self.dt.seconds())
+        } else {
+            particle.vel
+        }
+    }
+
+    /// Returns the kinetic energy in Joules, from the velocities at the time of the positions.
+    pub fn kinetic_energy(&self) -> f64 {
+        self.particles
+            .iter()
+            .map(|p| {
+                // E_kin = 1/2 * m * v^2
+                0.5 * p.mass * self.synchronized_velocity(p).norm().powi(2)
+            })
+            .sum()
+    }
+
+    /// Returns the energy in Joules that the thermostat conserves: the total energy of the
+    /// particles, plus the energy of the Nosé-Hoover thermostats. Its drift measures the error of
+    /// the integration. Without a thermostat, it is the total energy.
+    pub fn conserved_energy(&self) -> f64 {
+        let mut energy = self.potential_energy() + self.kinetic_energy();
+        if let Thermostat::NoseHoover { period, .. } = self.thermostat {
+            let dof = self.degrees_of_freedom();
+            energy += self.chain.energy(dof, self.temperature, period);
+        }
+        energy
+    }
+
+    /// Returns the number of degrees of freedom of the particles: three for each, less one for
+    /// every constraint (three for every rigid triangle), and three for the centre of mass if the
+    /// integrator conserves the momentum, since [`Universe::prepare`] has stopped it.
+    pub fn degrees_of_freedom(&self) -> f64 {
+        let constraints = self.topology.constraints.len() + 3 * self.topology.settles.len();
+        let centre_of_mass = if self.integrator.conserves_momentum() {
+            3
+        } else {
+            0
+        };
+        let free = 3 * self.particles.len() as isize - constraints as isize - centre_of_mass;
+        free.max(1) as f64
+    }
+
+    /// Returns the temperature in Kelvin that corresponds to the kinetic energy.
+    ///
+    /// ```
+    /// T = 2 * E_kin / (N_df * k_B)
+    /// ```
+    pub fn kinetic_temperature(&self) -> f64 {
+        2.0 * self.kinetic_energy() / (self.degrees_of_freedom() * BOLTZMANN)
+    }
+
+    /// Put particles that have left the box back in at the opposite side.
+    pub(crate) fn apply_boundary_conditions(&mut self) {
         for particle in &mut self.particles {
             let pos = &mut particle.pos;
             let bound = self.boundary;
@@ -171,55 +700,398 @@ impl Universe {
                 pos.z -= bound.z
             }
         }
+    }
 
-        // let total_kinetic_energy: f64 = self
-        //     .particles
-        //     .iter()
-        //     .map(|p| {
-        //         // E_kin = 1/2 * m * v^2
-        //         0.5 * p.mass * p.vel.norm().powi(2)
-        //     })
-        //     .sum();
-        // // T = 2/3 * 1/k_B * E_kin
-        // let temperature = (2.0 / 3.0) * INV_BOLTZMANN * total_kinetic_energy;
-        // eprintln!(
-        //     "temperature at {:06} ns is {temperature} K",
-        //     self.time.nanoseconds()
-        // );
+    /// Apply `n` time steps in succession.
+    pub fn steps(&mut self, n: usize) -> Result<(), ConstraintError> {
+        for _ in 0..n {
+            self.step()?;
+        }
+        Ok(())
+    }
+}
 
-        // Apply temperature control.
-        //
-        // E_kin = T / (2/3 * 1/k_B)
-        //       = 3/2 * k_B * T
-        //
-        // E_kin = 1/2 * m * v^2
-        //   v^2 = E_kin / (1/2 * m)
-        //       = 2 * E_kin / m
-        //
-        // |v| = sqrt(2 * E_kin / m)
-        //     = sqrt(2 * 3/2 * k_B * T / m)
-        //     = sqrt(3 * k_B * T / m)
-        //     = sqrt(two_ekin / m)   where two_ekin = 3 * k_B * T
-        let t = self.temperature / self.particles.len() as f64;
-        let two_ekin = 3.0 * BOLTZMANN * t;
-        for particle in &mut self.particles {
-            let new_norm = f64::sqrt(two_ekin / particle.mass);
-            let scaling_factor = new_norm / particle.vel.norm();
-            particle.vel = particle.vel * scaling_factor;
+#[cfg(test)]
+pub(crate) mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::integrator::Langevin;
+    use crate::neighbours::CellList;
+    use crate::potential::{Modifier, Potential};
+
+    /// Returns particles on a jittered cubic lattice of `n` by `n` by `n` sites around the origin,
+    /// with random velocities.
+    fn jittered_lattice(n: usize, spacing: f64) -> Vec<Particle> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let length = n as f64 * spacing;
+        let mut jitter = |scale: f64| rng.gen_range(-scale..scale);
+        let mut particles = Vec::new();
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    let site = Vec3::new(x as f64, y as f64, z as f64) * spacing
+                        - Vec3::one() * length * 0.5;
+                    let pos = site + Vec3::new(jitter(0.1), jitter(0.1), jitter(0.1)) * spacing;
+                    let vel = Vec3::new(jitter(100.0), jitter(100.0), jitter(100.0));
+                    particles.push(Particle::new(pos, vel, Vec3::zero(), 1e-24));
+                }
+            }
         }
+        particles
+    }
 
-        // Apply pressure control.
-        // TODO: Implement pressure control.
+    /// Returns a universe of particles on a jittered cubic lattice with random velocities.
+    fn lattice(n: usize, spacing: f64) -> Universe {
+        Universe::new(
+            Time::from_femtoseconds(1.0),
+            Vec3::one() * n as f64 * spacing,
+            300.0,
+        )
+        .thermostat(Thermostat::None)
+        .add_particles(&jittered_lattice(n, spacing))
+    }
 
-        // Increase time and iteration count.
-        self.time += self.dt;
-        self.iteration += 1;
+    /// Returns a universe of some particles in a cubic box of some edge `length` (meter), which
+    /// interact as argon up to the cutoff, with some modifier of the potential at the cutoff.
+    pub(crate) fn argon_universe(
+        particles: &[Particle],
+        length: f64,
+        cutoff: f64,
+        modifier: Modifier,
+    ) -> Universe {
+        let potential = LennardJones::from_molar(996.0, 0.34e-9);
+        Universe::new(Time::from_femtoseconds(1.0), Vec3::one() * length, 300.0)
+            .cutoff(cutoff)
+            .potentials(PairTable::single(potential).truncated(cutoff, modifier))
+            .thermostat(Thermostat::None)
+            .add_particles(particles)
     }
 
-    /// Apply `n` time steps in succession.
-    pub fn steps(&mut self, n: usize) {
-        for _ in 0..n {
-            self.step();
+    fn momentum(u: &Universe) -> Vec3 {
+        u.particles
+            .iter()
+            .fold(Vec3::zero(), |p, particle| p + particle.vel * particle.mass)
+    }
+
+    #[test]
+    fn momentum_is_conserved_without_thermostat() {
+        let mut u = lattice(6, 0.4e-9);
+        let before = momentum(&u);
+        u.steps(200).unwrap();
+        let after = momentum(&u);
+        // Compare to the scale of the momenta of the individual particles.
+        let scale: f64 = u
+            .particles
+            .iter()
+            .map(|particle| (particle.vel * particle.mass).norm())
+            .sum();
+        assert!(scale > 0.0);
+        assert!(
+            (after - before).norm() <= 1e-14 * scale,
+            "momentum changed from {before:?} to {after:?}"
+        );
+    }
+
+    /// Returns an argon-like fluid with some modifier of the potential at the cutoff.
+    pub(crate) fn argon(modifier: Modifier) -> Universe {
+        let (n, spacing) = (6, 0.4e-9);
+        let particles = jittered_lattice(n, spacing);
+        argon_universe(&particles, n as f64 * spacing, 2.5 * 0.34e-9, modifier)
+    }
+
+    /// Returns the largest deviation of the total energy from its initial value over an NVE run
+    /// of an argon-like fluid, relative to the kinetic energy.
+    fn energy_drift(modifier: Modifier) -> f64 {
+        let mut u = argon(modifier);
+        u.steps(1).unwrap();
+        let initial = u.potential_energy() + u.kinetic_energy();
+        let kinetic = u.kinetic_energy();
+        let mut drift: f64 = 0.0;
+        for _ in 0..500 {
+            u.step().unwrap();
+            let energy = u.potential_energy() + u.kinetic_energy();
+            drift = drift.max((energy - initial).abs());
+        }
+        drift / kinetic
+    }
+
+    #[test]
+    fn smooth_modifiers_drift_less() {
+        let none = energy_drift(Modifier::None);
+        let potential_shift = energy_drift(Modifier::PotentialShift);
+        let force_shift = energy_drift(Modifier::ForceShift);
+        let switch = energy_drift(Modifier::Switch { r_switch: 0.7e-9 });
+        assert!(potential_shift < none);
+        assert!(force_shift < none);
+        assert!(switch < none);
+    }
+
+    #[test]
+    fn prepare_rejects_a_cutoff_beyond_half_the_box() {
+        let particles = jittered_lattice(3, 0.4e-9);
+        let mut u = argon_universe(&particles, 1.2e-9, 0.7e-9, Modifier::None);
+        assert_eq!(
+            u.prepare(),
+            Err(PrepareError::CutoffTooLarge {
+                cutoff: 0.7e-9,
+                smallest_edge: 1.2e-9
+            })
+        );
+        let mut u = argon_universe(&particles, 1.2e-9, 0.6e-9, Modifier::None);
+        assert_eq!(u.prepare(), Ok(()));
+    }
+
+    #[test]
+    fn verlet_list_gives_the_forces_of_the_cell_list() {
+        let mut u = argon(Modifier::ForceShift);
+        u.steps(10).unwrap();
+        let builds = u.neighbour_list.builds();
+        while u.neighbour_list.builds() == builds {
+            u.step().unwrap();
+        }
+        let forces = u.compute_forces();
+
+        let cells = CellList::new(u.boundary, u.cutoff).build(&u.particles);
+        let mut expected = vec![Vec3::zero(); u.particles.len()];
+        for (index, particle) in u.particles.iter().enumerate() {
+            for other_index in cells.neighbours(particle.pos) {
+                let other = &u.particles[other_index];
+                let r = minimum_image(particle.pos - other.pos, u.boundary);
+                let distance = r.norm();
+                if index != other_index && distance < u.cutoff {
+                    let potential = u.potentials.get(particle.species, other.species);
+                    expected[index] += r * (potential.force(distance) / distance);
+                }
+            }
+        }
+        let scale = expected.iter().map(|f| f.norm()).fold(0.0, f64::max);
+        for (force, expected) in forces.iter().zip(&expected) {
+            assert!((*force - *expected).norm() < 1e-12 * scale);
+        }
+    }
+
+    #[test]
+    fn velocity_verlet_conserves_energy_over_long_runs() {
+        let mut u = argon(Modifier::ForceShift);
+        u.dt = Time::from_femtoseconds(5.0);
+        u.steps(1).unwrap();
+        let kinetic = u.kinetic_energy();
+        let energies: Vec<f64> = (0..10000)
+            .map(|_| {
+                u.step().unwrap();
+                u.potential_energy() + u.kinetic_energy()
+            })
+            .collect();
+        // The energy fluctuates around its initial value, but does not drift away from it.
+        let average = |energies: &[f64]| energies.iter().sum::<f64>() / energies.len() as f64;
+        let (first, last) = (average(&energies[..1000]), average(&energies[9000..]));
+        let fluctuation = energies
+            .iter()
+            .map(|energy| (energy - first).abs())
+            .fold(0.0, f64::max);
+        assert!((last - first).abs() < 1e-5 * kinetic, "{first} {last}");
+        assert!(fluctuation < 1e-4 * kinetic, "{fluctuation}");
+    }
+
+    #[test]
+    fn berendsen_keeps_the_distribution_of_velocities() {
+        let mut u = argon(Modifier::ForceShift).thermostat(Thermostat::Berendsen { tau: 0.1e-12 });
+        u.steps(4000).unwrap();
+        let (mut current, mut ratio) = (0.0, 0.0);
+        let samples = 100;
+        for _ in 0..samples {
+            u.steps(10).unwrap();
+            current += u.kinetic_temperature() / samples as f64;
+            // For a Maxwell-Boltzmann distribution, <v^4> / <v^2>^2 = 5/3, where rescaling every
+            // speed to the same norm would give 1.
+            let squares: Vec<f64> = u.particles.iter().map(|p| p.vel.norm().powi(2)).collect();
+            let n = squares.len() as f64;
+            let mean = squares.iter().sum::<f64>() / n;
+            let fourth = squares.iter().map(|v2| v2 * v2).sum::<f64>() / n;
+            ratio += fourth / (mean * mean) / samples as f64;
+        }
+        assert!(
+            (current - u.temperature).abs() < 0.02 * u.temperature,
+            "{current}"
+        );
+        assert!((ratio - 5.0 / 3.0).abs() < 0.1 * 5.0 / 3.0, "{ratio}");
+    }
+
+    #[test]
+    fn degrees_of_freedom_exclude_constraints_and_the_centre_of_mass() {
+        use crate::constraints::Constraint;
+        use crate::topology::Molecule;
+        use crate::water::WaterModel;
+
+        let dimer = Molecule {
+            name: "DIM".to_string(),
+            species: vec![0, 0],
+            bonds: Vec::new(),
+            angles: Vec::new(),
+            dihedrals: Vec::new(),
+            impropers: Vec::new(),
+            constraints: vec![Constraint {
+                particles: (0, 1),
+                length: 0.1e-9,
+            }],
+            settles: Vec::new(),
+        };
+        let mut topology = Topology::new();
+        topology.add_molecule(&WaterModel::Spce.molecule(0, 0), 0);
+        topology.add_molecule(&dimer, 3);
+        let particles = vec![Particle::new(Vec3::zero(), Vec3::zero(), Vec3::zero(), 1e-26); 6];
+        let u = lattice(2, 0.4e-9)
+            .topology(topology)
+            .add_particles(&particles);
+        // Three for each of 14 particles, less three for the water, one for the dimer, and three
+        // for the centre of mass.
+        assert_eq!(u.degrees_of_freedom(), 3.0 * 14.0 - 3.0 - 1.0 - 3.0);
+        // Langevin dynamics does not conserve the momentum.
+        let langevin = Langevin::new(1e12, 300.0, StdRng::seed_from_u64(4));
+        let u = u.integrator(Box::new(langevin));
+        assert_eq!(u.degrees_of_freedom(), 3.0 * 14.0 - 3.0 - 1.0);
+    }
+
+    #[test]
+    fn one_four_pairs_are_not_modified_at_the_cutoff() {
+        use crate::topology::{Bond, Molecule};
+
+        let (epsilon, sigma, cutoff, fudge) = (1e-21, 0.3e-9, 0.5e-9, 0.5);
+        let bonds = [(0, 1), (1, 2), (2, 3)].map(|particles| Bond {
+            particles,
+            k: 0.0,
+            r0: 0.1e-9,
+        });
+        let chain = Molecule {
+            name: "BUT".to_string(),
+            species: vec![0; 4],
+            bonds: bonds.to_vec(),
+            angles: Vec::new(),
+            dihedrals: Vec::new(),
+            impropers: Vec::new(),
+            constraints: Vec::new(),
+            settles: Vec::new(),
+        };
+        let modifiers = [
+            Modifier::None,
+            Modifier::PotentialShift,
+            Modifier::ForceShift,
+            Modifier::Switch { r_switch: 0.3e-9 },
+        ];
+        for modifier in modifiers {
+            // Within the switching region, and beyond the cutoff.
+            for r in [0.4e-9, 0.6e-9] {
+                let mut topology = Topology::new();
+                topology.add_molecule(&chain, 0);
+                let particles: Vec<Particle> = (0..4)
+                    .map(|i| {
+                        let pos = Vec3::new(i as f64 * r / 3.0, 0.0, 0.0);
+                        Particle::new(pos, Vec3::zero(), Vec3::zero(), 1e-26)
+                    })
+                    .collect();
+                let potential = LennardJones::new(epsilon, sigma);
+                let mut u = Universe::new(Time::from_femtoseconds(1.0), Vec3::one() * 2e-9, 300.0)
+                    .cutoff(cutoff)
+                    .potentials(PairTable::single(potential).truncated(cutoff, modifier))
+                    .fudge_factors(fudge, 1.0)
+                    .topology(topology)
+                    .add_particles(&particles);
+                u.compute_forces();
+                let expected = fudge * 4.0 * epsilon * ((sigma / r).powi(12) - (sigma / r).powi(6));
+                assert!(
+                    (u.potential_energy() - expected).abs() < 1e-12 * expected.abs(),
+                    "{modifier:?} at {r}: {} {expected}",
+                    u.potential_energy()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn dispersion_correction_adds_the_tails() {
+        let mut u = argon(Modifier::None);
+        u.compute_forces();
+        let (energy, pressure) = (u.potential_energy(), u.pressure());
+        u.dispersion_correction = true;
+        let n = u.particles.len() as f64;
+        let density = n / u.volume();
+        let potential = LennardJones::from_molar(996.0, 0.34e-9);
+        // 4 * π * ρ^2 * V / 2 * ∫ r^2 V(r) dr, and 4 * π * ρ^2 / 6 * ∫ r^3 F(r) dr.
+        let tail_energy = 0.5 * n * density * potential.tail_energy(u.cutoff);
+        let tail_pressure = density * density * potential.tail_virial(u.cutoff) / 6.0;
+        assert!(tail_energy < 0.0 && tail_pressure < 0.0);
+        let energy_change = u.potential_energy() - energy;
+        let pressure_change = u.pressure() - pressure;
+        assert!(
+            (energy_change - tail_energy).abs() < 1e-9 * tail_energy.abs(),
+            "{energy_change} {tail_energy}"
+        );
+        assert!(
+            (pressure_change - tail_pressure).abs() < 1e-9 * tail_pressure.abs(),
+            "{pressure_change} {tail_pressure}"
+        );
+    }
+
+    #[test]
+    fn prepare_stops_the_centre_of_mass() {
+        let mut u = argon(Modifier::ForceShift);
+        let scale: f64 = (u.particles.iter())
+            .map(|particle| (particle.vel * particle.mass).norm())
+            .sum();
+        assert!(momentum(&u).norm() > 1e-3 * scale);
+        u.prepare().unwrap();
+        assert!(momentum(&u).norm() < 1e-14 * scale, "{:?}", momentum(&u));
+    }
+
+    #[test]
+    fn nose_hoover_conserves_the_extended_energy() {
+        let thermostat = Thermostat::NoseHoover {
+            period: 0.2e-12,
+            chain: 3,
+        };
+        let mut u = argon(Modifier::ForceShift).thermostat(thermostat);
+        u.steps(1).unwrap();
+        let initial = u.conserved_energy();
+        let kinetic = u.kinetic_energy();
+        let (mut drift, mut change): (f64, f64) = (0.0, 0.0);
+        for _ in 0..1000 {
+            u.step().unwrap();
+            drift = drift.max((u.conserved_energy() - initial).abs());
+            let total = u.potential_energy() + u.kinetic_energy();
+            change = change.max((total - initial).abs());
+        }
+        // The thermostats exchange energy with the particles, but not with anything else.
+        assert!(change > 0.05 * kinetic, "{change}");
+        assert!(drift < 1e-3 * kinetic, "{drift}");
+    }
+
+    #[test]
+    fn nose_hoover_samples_the_canonical_ensemble() {
+        let thermostat = Thermostat::NoseHoover {
+            period: 0.2e-12,
+            chain: 3,
+        };
+        let mut u = argon(Modifier::ForceShift).thermostat(thermostat);
+        u.steps(2000).unwrap();
+        let samples = 1000;
+        let (mut mean, mut square) = (0.0, 0.0);
+        for _ in 0..samples {
+            u.steps(5).unwrap();
+            let current = u.kinetic_temperature();
+            mean += current / samples as f64;
+            square += current * current / samples as f64;
         }
+        // In the canonical ensemble, the kinetic energy fluctuates by sqrt(2 / N_df).
+        let fluctuation = (square - mean * mean).sqrt() / mean;
+        let expected = (2.0 / u.degrees_of_freedom()).sqrt();
+        assert!(
+            (mean - u.temperature).abs() < 0.02 * u.temperature,
+            "{mean}"
+        );
+        assert!(
+            (fluctuation - expected).abs() < 0.15 * expected,
+            "{fluctuation}"
+        );
     }
 }
@@ -0,0 +1,226 @@
+use crate::constraints::Settle;
+use crate::electrostatics::ELEMENTARY_CHARGE;
+use crate::potential::AVOGADRO;
+use crate::species::{Species, DALTON};
+use crate::topology::Molecule;
+
+/// Name of the molecule of a built-in water model.
+pub const WATER_MOLECULE: &str = "SOL";
+
+/// A built-in model of rigid three-site water.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaterModel {
+    /// Extended simple point charge model.
+    ///
+    /// See Berendsen, H. J. C., Grigera, J. R. & Straatsma, T. P. (1987). J. Phys. Chem. 91, 6269.
+    Spce,
+    /// Transferable intermolecular potential with three points.
+    ///
+    /// See Jorgensen, W. L., Chandrasekhar, J., Madura, J. D., Impey, R. W. & Klein, M. L.
+    /// (1983). J. Chem. Phys. 79, 926.
+    Tip3p,
+}
+
+/// Parameters of a water model, in the units of the recipe.
+struct Parameters {
+    /// Lennard-Jones σ of the oxygen (nm).
+    sigma: f64,
+    /// Lennard-Jones ε of the oxygen (kJ/mol).
+    epsilon: f64,
+    /// Charge of the hydrogens (e). The oxygen carries twice the opposite charge.
+    charge: f64,
+    /// Distance between the oxygen and either hydrogen (nm).
+    oh: f64,
+    /// Angle between the two bonds (degrees).
+    angle: f64,
+}
+
+impl WaterModel {
+    fn parameters(&self) -> Parameters {
+        match self {
+            WaterModel::Spce => Parameters {
+                sigma: 0.316557,
+                epsilon: 0.650194,
+                charge: 0.4238,
+                oh: 0.1,
+                angle: 109.47,
+            },
+            WaterModel::Tip3p => Parameters {
+                sigma: 0.315061,
+                epsilon: 0.636386,
+                charge: 0.417,
+                oh: 0.09572,
+                angle: 104.52,
+            },
+        }
+    }
+
+    /// Returns the species of the oxygen (`OW`) and of the hydrogens (`HW`), which have no
+    /// Lennard-Jones interactions.
+    pub fn species(&self) -> [Species; 2] {
+        let parameters = self.parameters();
+        let charge = parameters.charge * ELEMENTARY_CHARGE;
+        [
+            Species {
+                name: "OW".to_string(),
+                mass: 15.9994 * DALTON,
+                sigma: parameters.sigma * 1e-9,
+                epsilon: parameters.epsilon * 1e3 / AVOGADRO,
+                charge: -2.0 * charge,
+            },
+            Species {
+                name: "HW".to_string(),
+                mass: 1.008 * DALTON,
+                sigma: 0.0,
+                epsilon: 0.0,
+                charge,
+            },
+        ]
+    }
+
+    /// Returns the rigid water molecule, given the indices of the species of its oxygen and
+    /// hydrogens.
+    pub fn molecule(&self, oxygen: usize, hydrogen: usize) -> Molecule {
+        let parameters = self.parameters();
+        let oh = parameters.oh * 1e-9;
+        let hh = 2.0 * oh * (0.5 * parameters.angle.to_radians()).sin();
+        Molecule {
+            name: WATER_MOLECULE.to_string(),
+            species: vec![oxygen, hydrogen, hydrogen],
+            bonds: Vec::new(),
+            angles: Vec::new(),
+            dihedrals: Vec::new(),
+            impropers: Vec::new(),
+            constraints: Vec::new(),
+            settles: vec![Settle {
+                particles: (0, 1, 2),
+                oh,
+                hh,
+            }],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::electrostatics::{
+        Electrostatics, Pme, DEFAULT_EWALD_TOLERANCE, DEFAULT_PME_ORDER, DEFAULT_PME_SPACING,
+    };
+    use crate::potential::Modifier;
+    use crate::species::{lennard_jones_table, MixingRule};
+    use crate::time::Time;
+    use crate::topology::Topology;
+    use crate::universe::{Particle, Thermostat, Universe};
+    use crate::vec3::Vec3;
+
+    /// Runs SPC/E water at 300 K and its density of 0.997 g/cm^3 at 1 bar, with `per_edge` cubed
+    /// molecules, and returns the average temperature (K), potential energy (kJ/mol per molecule)
+    /// and pressure (bar) over `steps` steps after equilibrating for `equilibration` steps.
+    fn spce(per_edge: usize, cutoff: f64, equilibration: usize, steps: usize) -> (f64, f64, f64) {
+        let model = WaterModel::Spce;
+        let species = model.species();
+        let molecule = model.molecule(0, 1);
+        let settle = molecule.settles[0];
+
+        let n = per_edge * per_edge * per_edge;
+        let mass: f64 = molecule.species.iter().map(|&s| species[s].mass).sum();
+        let length = (n as f64 * mass / 997.0).cbrt();
+        let spacing = length / per_edge as f64;
+        let temperature = 300.0;
+
+        // Start from a lattice of equally oriented molecules with random velocities.
+        let mut rng = StdRng::seed_from_u64(17);
+        let mut particles = Vec::new();
+        let mut topology = Topology::new();
+        let half_angle = (0.5 * settle.hh / settle.oh).asin();
+        let (sin, cos) = (2.0 * half_angle).sin_cos();
+        for index in 0..n {
+            let (x, y, z) = (
+                index % per_edge,
+                index / per_edge % per_edge,
+                index / per_edge.pow(2),
+            );
+            let site =
+                Vec3::new(x as f64, y as f64, z as f64) * spacing - Vec3::one() * (0.5 * length);
+            let hydrogen = Vec3::new(settle.oh, 0.0, 0.0);
+            let other = Vec3::new(settle.oh * cos, settle.oh * sin, 0.0);
+            topology.add_molecule(&molecule, particles.len());
+            for (pos, &s) in [site, site + hydrogen, site + other]
+                .into_iter()
+                .zip(&molecule.species)
+            {
+                let mut random = || rng.gen_range(-500.0..500.0);
+                let vel = Vec3::new(random(), random(), random());
+                let particle = Particle::new(pos, vel, Vec3::zero(), species[s].mass);
+                particles.push(particle.species(s).charge(species[s].charge));
+            }
+        }
+
+        let boundary = Vec3::one() * length;
+        let pme = Pme::new(
+            cutoff,
+            boundary,
+            DEFAULT_EWALD_TOLERANCE,
+            DEFAULT_PME_SPACING,
+            DEFAULT_PME_ORDER,
+        );
+        let mut u = Universe::new(Time::from_femtoseconds(2.0), boundary, temperature)
+            .cutoff(cutoff)
+            .potentials(
+                lennard_jones_table(&species, MixingRule::LorentzBerthelot, &[])
+                    .truncated(cutoff, Modifier::None),
+            )
+            .electrostatics(Electrostatics::Pme(pme))
+            .dispersion_correction(true)
+            .topology(topology)
+            .thermostat(Thermostat::Berendsen { tau: 0.1e-12 })
+            .add_particles(&particles);
+        u.prepare().unwrap();
+
+        // Six degrees of freedom per rigid molecule, less those of the centre of mass.
+        assert_eq!(u.degrees_of_freedom(), 6.0 * n as f64 - 3.0);
+        u.steps(equilibration).unwrap();
+        let mut energy = 0.0;
+        let mut pressure = 0.0;
+        let mut current = 0.0;
+        for _ in 0..steps {
+            u.step().unwrap();
+            current += u.kinetic_temperature() / steps as f64;
+            energy += u.potential_energy() / steps as f64;
+            pressure += u.pressure() / steps as f64;
+        }
+        let energy = energy / n as f64 * AVOGADRO * 1e-3;
+        let pressure = pressure * 1e-5;
+        (current, energy, pressure)
+    }
+
+    /// Compares the average potential energy and pressure of SPC/E water to those known for the
+    /// model, about -46.7 kJ/mol, or -41.5 kJ/mol after the correction for the polarization.
+    ///
+    /// There is no barostat, so the density is not free to change, and it is only checked through
+    /// the pressure at the density of 0.997 g/cm^3: within 500 bar of zero corresponds to within
+    /// about 2 % of the density at 1 bar. The 64 molecules here fluctuate too much for that, and
+    /// only fit a short cutoff, so the bounds are wider than for
+    /// [`spce_matches_known_density_and_energy_in_a_larger_box`].
+    #[test]
+    fn spce_matches_known_density_and_energy() {
+        let (current, energy, pressure) = spce(4, 0.6e-9, 1000, 1000);
+        assert!((current - 300.0).abs() < 10.0, "T = {current:.1} K");
+        assert!((energy + 46.7).abs() < 1.5, "U = {energy:.2} kJ/mol");
+        assert!(pressure.abs() < 1500.0, "P = {pressure:.0} bar");
+    }
+
+    /// As [`spce_matches_known_density_and_energy`], with 216 molecules over a longer run. This
+    /// takes minutes, so run it with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn spce_matches_known_density_and_energy_in_a_larger_box() {
+        let (current, energy, pressure) = spce(6, 0.9e-9, 1500, 3000);
+        assert!((current - 300.0).abs() < 5.0, "T = {current:.1} K");
+        assert!((energy + 46.7).abs() < 1.0, "U = {energy:.2} kJ/mol");
+        assert!(pressure.abs() < 500.0, "P = {pressure:.0} bar");
+    }
+}